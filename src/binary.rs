@@ -1,7 +1,12 @@
 use beef::Cow;
-use semver::Version as SemVersion;
+use miette::Result;
+use semver::{Version as SemVersion, VersionReq};
 use std::fmt::Display;
+use std::path::PathBuf;
+use std::time::Duration;
 
+use crate::error::ConfigError;
+use crate::parser::{Entry, ProbeOverride, Stream};
 use crate::versions::{format_version, unknown_version};
 
 #[derive(Debug)]
@@ -10,6 +15,48 @@ pub struct Binary<'a> {
   // TODO: use a custom version type
   pub version: Option<SemVersion>,
   pub package_manager: Option<String>,
+  pub requirement: Option<VersionReq>,
+  /// All names of an `a | b` group, `name` is whichever of them was found first
+  pub alternatives: Vec<Cow<'a, str>>,
+  /// Missing or outdated optional binaries are reported but never fail the check
+  pub optional: bool,
+  /// The needsfile `[section]` the binary was listed under
+  pub group: Option<String>,
+  /// How to get the version if the defaults don't work for this binary
+  pub probe: ProbeOverride,
+  /// How the version was probed, `None` if the binary wasn't run
+  pub probed: Option<Probed>,
+  /// The version probe didn't finish within `--probe-timeout`
+  pub timed_out: bool,
+  /// Where the binary was found
+  pub path: Option<PathBuf>,
+  /// The needsfile and line the binary is listed on, `None` for binaries given as arguments
+  pub file: Option<PathBuf>,
+  pub line: Option<usize>,
+  /// Other copies further down the PATH, `None` unless looked up with `--all`
+  pub shadowed: Option<Vec<Shadowed>>,
+}
+
+/// A copy of a binary that is hidden by the one found first on the PATH
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shadowed {
+  pub path: PathBuf,
+  pub package_manager: Option<String>,
+  pub version: Option<SemVersion>,
+}
+
+/// How the version of a binary was probed, for the reports
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probed {
+  /// The arguments the binary was run with
+  pub args: Vec<String>,
+  /// The output the version was found on, `None` if there was no version
+  pub stream: Option<Stream>,
+  /// The version as the binary printed it, before it was cleaned up for semver
+  pub raw_version: Option<String>,
+  pub duration: Duration,
+  /// Taken from the version cache instead of running the binary
+  pub cached: bool,
 }
 
 impl<'a> Binary<'a> {
@@ -18,14 +65,92 @@ impl<'a> Binary<'a> {
       name,
       version: None,
       package_manager: None,
+      requirement: None,
+      alternatives: Vec::new(),
+      optional: false,
+      group: None,
+      probe: ProbeOverride::default(),
+      probed: None,
+      timed_out: false,
+      path: None,
+      file: None,
+      line: None,
+      shadowed: None,
+    }
+  }
+
+  pub fn new_with_requirement(name: Cow<'a, str>, requirement: Option<VersionReq>) -> Self {
+    Self {
+      requirement,
+      ..Self::new(name)
     }
   }
 
   pub fn new_with_package_manager(name: Cow<'a, str>, package_manager: Option<String>) -> Self {
     Self {
-      name,
-      version: None,
       package_manager,
+      ..Self::new(name)
+    }
+  }
+
+  pub fn from_entry(entry: Entry) -> Self {
+    Self {
+      alternatives: entry.alternatives.into_iter().map(Cow::owned).collect(),
+      optional: entry.optional,
+      group: entry.group,
+      probe: entry.probe,
+      file: entry.file,
+      line: entry.line,
+      ..Self::new_with_requirement(Cow::owned(entry.name), entry.requirement)
+    }
+  }
+
+  /// The names to look for, in order of preference
+  pub fn candidates(&self) -> Vec<Cow<'a, str>> {
+    if self.alternatives.is_empty() {
+      vec![self.name.clone()]
+    } else {
+      self.alternatives.clone()
+    }
+  }
+
+  /// The name as written in the needsfile, e.g. `fd | fdfind` for alternatives
+  pub fn display_name(&self) -> String {
+    if self.alternatives.is_empty() {
+      self.name.to_string()
+    } else {
+      self.alternatives.join(" | ")
+    }
+  }
+
+  /// A copy further down the PATH has a higher version than the one that is used
+  pub fn shadows_newer(&self) -> bool {
+    self
+      .shadowed
+      .iter()
+      .flatten()
+      .any(|copy| self.is_newer(copy))
+  }
+
+  /// The copy has a higher version than this binary, `false` if either version is unknown
+  pub fn is_newer(&self, copy: &Shadowed) -> bool {
+    match (&self.version, &copy.version) {
+      (Some(version), Some(other)) => other.cmp_precedence(version).is_gt(),
+      _ => false,
+    }
+  }
+
+  /// Whether the detected version fulfills the requirement from the needsfile.
+  /// Binaries without a requirement always pass, unknown versions never do.
+  pub fn meets_requirement(&self) -> bool {
+    match (&self.requirement, &self.version) {
+      (None, _) => true,
+      (Some(_), None) => false,
+      (Some(req), Some(version)) => {
+        // prerelease and build metadata would make `matches` reject e.g. `1.3.0-VERGEN`
+        let plain = SemVersion::new(version.major, version.minor, version.patch);
+        req.matches(&plain)
+      }
     }
   }
 }
@@ -36,29 +161,161 @@ impl Default for Binary<'_> {
       name: Cow::borrowed(""),
       version: Some(unknown_version()),
       package_manager: None,
+      requirement: None,
+      alternatives: Vec::new(),
+      optional: false,
+      group: None,
+      probe: ProbeOverride::default(),
+      probed: None,
+      timed_out: false,
+      path: None,
+      file: None,
+      line: None,
+      shadowed: None,
     }
   }
 }
 
 impl Display for Binary<'_> {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    if self.version.is_none() {
-      if let Some(ref pm) = self.package_manager {
-        write!(f, "{} ? ({})", self.name, pm)
-      } else {
-        write!(f, "{} ?", self.name)
+    match self.version {
+      None => {
+        let unknown = if self.timed_out { "timed out" } else { "?" };
+        if let Some(ref pm) = self.package_manager {
+          write!(f, "{} {} ({})", self.name, unknown, pm)
+        } else {
+          write!(f, "{} {}", self.name, unknown)
+        }
       }
-    } else {
-      let version_str = format_version(self.version.as_ref().unwrap(), false);
-      if let Some(ref pm) = self.package_manager {
-        write!(f, "{} {} ({})", self.name, version_str, pm)
-      } else {
-        write!(f, "{} {}", self.name, version_str)
+      Some(ref version) => {
+        let version_str = format_version(version, false);
+        if let Some(ref pm) = self.package_manager {
+          write!(f, "{} {} ({})", self.name, version_str, pm)
+        } else {
+          write!(f, "{} {}", self.name, version_str)
+        }
       }
     }
   }
 }
 
 pub fn sort_binaries(binaries: &mut Vec<Binary>) {
-  binaries.sort_by(|a, b| a.name.cmp(&b.name))
+  binaries.sort_by(compare_binaries)
+}
+
+/// The order of [`sort_binaries`], for lists that hold more than the binaries
+pub fn compare_binaries(a: &Binary, b: &Binary) -> std::cmp::Ordering {
+  a.name.cmp(&b.name)
+}
+
+/// The needsfile sections of the binaries, in order of first appearance
+pub fn group_names(binaries: &[Binary]) -> Vec<String> {
+  let mut groups: Vec<String> = Vec::new();
+  for group in binaries.iter().filter_map(|bin| bin.group.as_ref()) {
+    if !groups.contains(group) {
+      groups.push(group.clone());
+    }
+  }
+  groups
+}
+
+/// Keep only the binaries listed under one of the given needsfile sections
+pub fn filter_groups<'a>(binaries: Vec<Binary<'a>>, groups: &[String]) -> Result<Vec<Binary<'a>>> {
+  let known_groups = group_names(&binaries);
+
+  if let Some(unknown) = groups.iter().find(|group| !known_groups.contains(group)) {
+    return Err(
+      ConfigError::Invalid {
+        reason: format!("unknown group '{}'", unknown),
+        advice: if known_groups.is_empty() {
+          "The needsfile has no [sections].".to_string()
+        } else {
+          format!("Available groups: {}", known_groups.join(", "))
+        },
+      }
+      .into(),
+    );
+  }
+
+  Ok(
+    binaries
+      .into_iter()
+      .filter(|bin| {
+        bin
+          .group
+          .as_ref()
+          .is_some_and(|group| groups.contains(group))
+      })
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn grouped(name: &'static str, group: Option<&str>) -> Binary<'static> {
+    Binary {
+      group: group.map(str::to_string),
+      ..Binary::new(Cow::borrowed(name))
+    }
+  }
+
+  #[test]
+  fn test_meets_requirement() {
+    let mut bin = Binary::new_with_requirement(
+      Cow::borrowed("viddy"),
+      Some(VersionReq::parse(">=1.3").unwrap()),
+    );
+    assert!(!bin.meets_requirement(), "unknown versions never pass");
+    bin.version = Some(SemVersion::parse("1.3.0-VERGEN").unwrap());
+    assert!(bin.meets_requirement());
+    bin.version = Some(SemVersion::parse("1.2.9").unwrap());
+    assert!(!bin.meets_requirement());
+  }
+
+  #[test]
+  fn test_shadows_newer() {
+    let copy = |version: Option<SemVersion>| Shadowed {
+      path: PathBuf::from("/usr/local/bin/python"),
+      package_manager: None,
+      version,
+    };
+    let mut bin = Binary {
+      version: Some(SemVersion::new(3, 9, 6)),
+      shadowed: Some(vec![copy(Some(SemVersion::new(3, 8, 0))), copy(None)]),
+      ..Binary::new(Cow::borrowed("python"))
+    };
+    assert!(!bin.shadows_newer());
+
+    bin.shadowed = Some(vec![copy(Some(SemVersion::new(3, 12, 1)))]);
+    assert!(bin.shadows_newer());
+
+    bin.version = None;
+    assert!(!bin.shadows_newer());
+  }
+
+  #[test]
+  fn test_filter_groups() {
+    let bins = vec![
+      grouped("git", None),
+      grouped("cargo", Some("build")),
+      grouped("taplo", Some("lint")),
+      grouped("just", Some("build")),
+    ];
+    assert_eq!(group_names(&bins), vec!["build", "lint"]);
+
+    let filtered = filter_groups(bins, &["build".to_string()]).unwrap();
+    let names = filtered.iter().map(|b| b.name.as_ref()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["cargo", "just"]);
+  }
+
+  #[test]
+  fn test_filter_groups_unknown() {
+    let bins = vec![grouped("cargo", Some("build"))];
+    let result = filter_groups(bins, &["docs".to_string()]);
+    assert!(result.is_err());
+    let err_string = format!("{:?}", result.unwrap_err());
+    assert!(err_string.contains("needs::config::invalid_config"));
+  }
 }
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use log::{debug, info, warn};
+use miette::Result;
+use semver::Version as SemVersion;
+use serde::{Deserialize, Serialize};
+
+use crate::error::IoError;
+use crate::parser::Stream;
+use crate::probes::Probe;
+use crate::versions::FoundVersion;
+
+const CACHE_FILE: &str = "versions.json";
+
+/// `$XDG_CACHE_HOME/needs` or `~/.cache/needs`
+pub fn cache_dir() -> Option<PathBuf> {
+  let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+    .filter(|dir| !dir.is_empty())
+    .map(PathBuf::from)
+    .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+  Some(cache_dir.join("needs"))
+}
+
+/// What identifies a binary on disk, a changed binary gets probed again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Identity {
+  size: u64,
+  mtime_secs: u64,
+  mtime_nanos: u32,
+  inode: u64,
+}
+
+impl Identity {
+  fn of(path: &Path) -> Option<Self> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    #[cfg(unix)]
+    let inode = std::os::unix::fs::MetadataExt::ino(&metadata);
+    #[cfg(not(unix))]
+    let inode = 0;
+    Some(Self {
+      size: metadata.len(),
+      mtime_secs: mtime.as_secs(),
+      mtime_nanos: mtime.subsec_nanos(),
+      inode,
+    })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CachedVersion {
+  identity: Identity,
+  /// The probe that found the version, a different `version-cmd` or regex probes again
+  probe: String,
+  version: String,
+  /// The version as the binary printed it
+  #[serde(default)]
+  raw: String,
+  stream: Stream,
+}
+
+fn probe_key(probe: &Probe) -> String {
+  format!("{:?} {} {:?}", probe.args, probe.stream, probe.regex)
+}
+
+/// Whether `path` is a shim or proxy that picks the real binary at runtime, its version can change
+/// while the file stays the same. Covers the `shims` directories of pyenv, rbenv, asdf and mise,
+/// links to `rustup` or `mise` and rustup's hardlinked proxies.
+fn is_shim(path: &Path) -> bool {
+  if path
+    .components()
+    .any(|component| component.as_os_str() == "shims")
+  {
+    return true;
+  }
+  let proxies = ["rustup", "mise"].map(|name| format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+  let canonical = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+  if canonical
+    .file_name()
+    .is_some_and(|name| proxies.iter().any(|proxy| name == proxy.as_str()))
+  {
+    return true;
+  }
+  let Some(rustup) = path.parent().map(|dir| dir.join(&proxies[0])) else {
+    return false;
+  };
+  rustup != path && rustup.exists() && Identity::of(&rustup) == Identity::of(path)
+}
+
+/// Versions found by earlier runs, keyed by the path the binary was found at.
+/// Not the canonical path, proxies like rustup's `cargo` and `rustc` are the same file.
+/// Shims aren't cached at all, see [`is_shim`].
+#[derive(Debug, Default)]
+pub struct VersionCache {
+  file: Option<PathBuf>,
+  entries: Mutex<HashMap<PathBuf, CachedVersion>>,
+}
+
+impl VersionCache {
+  /// Read the cache file, a missing or broken one gives an empty cache
+  pub fn load() -> Self {
+    match cache_dir() {
+      Some(dir) => Self::load_from(dir.join(CACHE_FILE)),
+      None => {
+        debug!("no cache directory");
+        Self::default()
+      }
+    }
+  }
+
+  pub fn load_from(file: PathBuf) -> Self {
+    let entries = match std::fs::read_to_string(&file) {
+      Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+        warn!(path:display = file.display(), error:display = err; "ignoring broken version cache");
+        HashMap::new()
+      }),
+      Err(err) => {
+        debug!(path:display = file.display(), error:display = err; "no version cache yet");
+        HashMap::new()
+      }
+    };
+    Self {
+      file: Some(file),
+      entries: Mutex::new(entries),
+    }
+  }
+
+  /// The cached version of the binary at `path`, if it hasn't changed since
+  pub fn get(&self, path: &Path, probe: &Probe) -> Option<FoundVersion> {
+    if is_shim(path) {
+      debug!(path:display = path.display(); "not caching the version of a shim");
+      return None;
+    }
+    let entries = self.entries.lock().ok()?;
+    let cached = entries.get(path)?;
+    if Some(&cached.identity) != Identity::of(path).as_ref() || cached.probe != probe_key(probe) {
+      debug!(path:display = path.display(); "cached version is stale");
+      return None;
+    }
+    let version = SemVersion::parse(&cached.version).ok()?;
+    info!(path:display = path.display(), version:display = version; "version from cache");
+    Some(FoundVersion {
+      version,
+      raw: cached.raw.clone(),
+      stream: cached.stream,
+    })
+  }
+
+  pub fn insert(&self, path: &Path, probe: &Probe, found: &FoundVersion) {
+    let Some(identity) = Identity::of(path).filter(|_| !is_shim(path)) else {
+      return;
+    };
+    if let Ok(mut entries) = self.entries.lock() {
+      entries.insert(
+        path.to_path_buf(),
+        CachedVersion {
+          identity,
+          probe: probe_key(probe),
+          version: found.version.to_string(),
+          raw: found.raw.clone(),
+          stream: found.stream,
+        },
+      );
+    }
+  }
+
+  /// Write the cache back, through a temporary file so that parallel runs don't see half of it
+  pub fn save(&self) -> Result<()> {
+    let Some(ref file) = self.file else {
+      return Ok(());
+    };
+    let Ok(entries) = self.entries.lock() else {
+      return Ok(());
+    };
+    let write = |path: &Path, content: &str| -> std::io::Result<()> {
+      if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+      }
+      std::fs::write(path, content)
+    };
+    let content = serde_json::to_string(&*entries).map_err(std::io::Error::other);
+    let tmp = file.with_extension(format!("json.{}", std::process::id()));
+    content
+      .and_then(|content| write(&tmp, &content))
+      .and_then(|_| std::fs::rename(&tmp, file))
+      .map_err(|source| IoError::FileWrite {
+        path: file.clone(),
+        source,
+      })?;
+    debug!(path:display = file.display(), count = entries.len(); "saved version cache");
+    Ok(())
+  }
+}
+
+/// `needs cache clear`
+pub fn clear() -> Result<()> {
+  let Some(dir) = cache_dir() else {
+    return Ok(());
+  };
+  match std::fs::remove_dir_all(&dir) {
+    Ok(()) => {
+      info!(path:display = dir.display(); "cleared version cache");
+      Ok(())
+    }
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+    Err(source) => Err(IoError::FileWrite { path: dir, source }.into()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_utils::TempTree;
+
+  #[test]
+  fn test_cache_hit_and_stale() {
+    let dir = TempTree::new("cache");
+    let bin = dir.join("tool");
+    std::fs::write(&bin, "v1").unwrap();
+
+    let cache = VersionCache::load_from(dir.join(CACHE_FILE));
+    let probe = Probe::default();
+    let found = FoundVersion {
+      version: SemVersion::new(1, 2, 3),
+      raw: "v1.2.3".to_string(),
+      stream: Stream::Stderr,
+    };
+    assert_eq!(cache.get(&bin, &probe), None);
+    cache.insert(&bin, &probe, &found);
+    assert_eq!(cache.get(&bin, &probe), Some(found.clone()));
+
+    // a different probe or a changed binary isn't a hit
+    let other_probe = Probe {
+      args: vec!["-V".into()],
+      ..Probe::default()
+    };
+    assert_eq!(cache.get(&bin, &other_probe), None);
+
+    cache.save().unwrap();
+    let loaded = VersionCache::load_from(dir.join(CACHE_FILE));
+    assert_eq!(loaded.get(&bin, &probe), Some(found));
+
+    std::fs::write(&bin, "version two").unwrap();
+    assert_eq!(loaded.get(&bin, &probe), None);
+  }
+
+  #[test]
+  fn test_shims_are_not_cached() {
+    let dir = TempTree::new("shims");
+    std::fs::create_dir_all(dir.join("shims")).unwrap();
+    std::fs::create_dir_all(dir.join("bin")).unwrap();
+    let shim = dir.join("shims/python");
+    std::fs::write(&shim, "shim").unwrap();
+    let rustup = dir.join("bin/rustup");
+    std::fs::write(&rustup, "rustup").unwrap();
+    let proxy = dir.join("bin/cargo");
+    std::fs::hard_link(&rustup, &proxy).unwrap();
+    let tool = dir.join("bin/tool");
+    std::fs::write(&tool, "tool").unwrap();
+
+    let cache = VersionCache::load_from(dir.join(CACHE_FILE));
+    let probe = Probe::default();
+    let found = FoundVersion {
+      version: SemVersion::new(1, 2, 3),
+      raw: "1.2.3".to_string(),
+      stream: Stream::Stdout,
+    };
+    for path in [&shim, &rustup, &proxy, &tool] {
+      cache.insert(path, &probe, &found);
+    }
+    assert_eq!(cache.get(&shim, &probe), None);
+    assert_eq!(cache.get(&rustup, &probe), None);
+    assert_eq!(cache.get(&proxy, &probe), None);
+    assert_eq!(cache.get(&tool, &probe), Some(found));
+  }
+}
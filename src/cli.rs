@@ -1,6 +1,11 @@
 use crate::error::ConfigError;
-use clap::Parser;
+use crate::formats::Format;
+use crate::output::Layout;
+use crate::terminal::ColorChoice;
+use clap::{Parser, Subcommand};
 use miette::Result;
+use std::path::PathBuf;
+use std::time::Duration;
 
 pub const CLAP_STYLING: clap::builder::styling::Styles = clap::builder::styling::Styles::styled()
   .header(clap_cargo::style::HEADER)
@@ -16,8 +21,12 @@ pub const CLAP_STYLING: clap::builder::styling::Styles = clap::builder::styling:
 #[command(styles = CLAP_STYLING)]
 /// Check if given bin(s) are available in the PATH
 ///
-/// If no binaries are specified, it will look for a file named `needsfile` or `.needsfile` in the current directory.
+/// If no binaries are specified, it will look for a file named `needsfile` or `.needsfile` in the current directory
+/// and its parents, up to the repository root.
 pub struct Cli {
+  #[command(subcommand)]
+  pub command: Option<Command>,
+
   /// List of binaries to check
   pub bins: Option<Vec<String>>,
 
@@ -25,17 +34,199 @@ pub struct Cli {
   #[clap(short, long)]
   pub quiet: bool,
 
+  /// read the binaries from this needsfile instead of searching for one, `-` reads from stdin (can be repeated)
+  #[clap(short = 'f', long = "file", global = true, value_name = "PATH")]
+  pub files: Vec<PathBuf>,
+
+  /// only check the binaries of these needsfile sections, e.g. `--group build,lint`
+  #[clap(short, long, global = true, value_delimiter = ',')]
+  pub group: Option<Vec<String>>,
+
+  /// how to print the report
+  #[clap(long, value_enum, default_value_t)]
+  pub format: Format,
+
+  /// print one line per binary from a template like `{name}\t{version}\t{path}`, or the name of one in `templates.toml`
+  ///
+  /// Placeholders: {name}, {version}, {full_version}, {package_manager}, {path}, {status}
+  #[clap(long, conflicts_with = "format")]
+  pub template: Option<String>,
+
+  /// how to lay out the report, `grid` puts the binaries side by side
+  #[clap(long, value_enum, default_value_t)]
+  pub layout: Layout,
+
+  /// when to use colors
+  #[clap(long, value_enum, default_value_t, value_name = "WHEN")]
+  pub color: ColorChoice,
+
   /// Verbosity level (can be repeated, e.g. -vvv)
   #[clap(short, long, action = clap::ArgAction::Count)]
   pub verbosity: u8,
 
   #[cfg(feature = "version-retrieval")]
   /// don't check for versions
-  #[clap(short, long)]
+  #[clap(short, long, global = true)]
   pub no_versions: bool,
 
   #[cfg(feature = "version-retrieval")]
   /// show the full version string
-  #[clap(short, long)]
+  #[clap(short = 'F', long, global = true)]
   pub full_versions: bool,
+
+  #[cfg(feature = "version-retrieval")]
+  /// how long a binary may take to print its version, e.g. `2s` or `500ms`
+  #[clap(long, global = true, value_name = "DURATION", default_value = "5s", value_parser = parse_duration)]
+  pub probe_timeout: Duration,
+
+  #[cfg(feature = "version-retrieval")]
+  /// always call the binaries, don't use or update the version cache
+  #[clap(long, global = true)]
+  pub no_cache: bool,
+
+  /// list every copy of a binary on the PATH, not just the first one
+  #[clap(short, long)]
+  pub all: bool,
+
+  #[cfg(feature = "version-retrieval")]
+  /// exit with 1 if the first copy of a binary hides a newer one further down the PATH, implies `--all`
+  #[clap(long)]
+  pub warn_shadowed: bool,
+}
+
+impl Cli {
+  /// `needs which <bin>` is `needs --all <bin>`, and `--warn-shadowed` implies `--all`
+  pub fn resolve_shorthands(&mut self) {
+    if let Some(Command::Which { ref bins }) = self.command {
+      self.bins = Some(bins.clone());
+      self.all = true;
+    }
+    #[cfg(feature = "version-retrieval")]
+    {
+      self.all |= self.warn_shadowed;
+    }
+  }
+}
+
+/// Parse `500ms`, `2s`, `1.5s` or `1m`, a bare number is taken as seconds
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+  let value = value.trim();
+  let split = value
+    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+    .unwrap_or(value.len());
+  let (number, unit) = value.split_at(split);
+  let number: f64 = number
+    .parse()
+    .map_err(|_| format!("`{}` is not a duration, try `2s` or `500ms`", value))?;
+  let seconds = match unit.trim() {
+    "ms" => number / 1000.0,
+    "" | "s" => number,
+    "m" => number * 60.0,
+    other => return Err(format!("unknown unit `{}`, use `ms`, `s` or `m`", other)),
+  };
+  Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+  /// check a needsfile for duplicates, builtins, unreachable platforms and lines that don't parse
+  Lint {
+    /// the needsfile to check, defaults to the one `needs` would use
+    path: Option<PathBuf>,
+  },
+  /// sort the entries of a needsfile and align its comments
+  Fmt {
+    /// the needsfile to format, defaults to the one `needs` would use
+    path: Option<PathBuf>,
+
+    /// don't write anything, exit with 1 if the file isn't formatted
+    #[clap(long)]
+    check: bool,
+  },
+  #[cfg(feature = "version-retrieval")]
+  /// write `needs.lock` with the path, package manager and version of every binary
+  Lock {
+    /// where to write the lockfile, defaults to `needs.lock` next to the needsfile
+    path: Option<PathBuf>,
+  },
+  #[cfg(feature = "version-retrieval")]
+  /// compare this machine against `needs.lock`, exit with 1 if a binary was added, removed, upgraded or downgraded
+  Verify {
+    /// the lockfile to compare against, defaults to `needs.lock` next to the needsfile
+    path: Option<PathBuf>,
+  },
+  /// list every copy of the binaries on the PATH with its version, like `needs --all`
+  ///
+  /// Subcommand names take precedence over binary names, check a binary called `which` with `needs -- which`.
+  Which {
+    #[clap(required = true)]
+    bins: Vec<String>,
+  },
+  #[cfg(feature = "version-retrieval")]
+  /// manage the cache of versions from earlier runs
+  Cache {
+    #[command(subcommand)]
+    action: CacheCommand,
+  },
+}
+
+#[cfg(feature = "version-retrieval")]
+#[derive(Subcommand)]
+pub enum CacheCommand {
+  /// delete all cached versions
+  Clear,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_duration() {
+    assert_eq!(parse_duration("2s"), Ok(Duration::from_secs(2)));
+    assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+    assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+    assert_eq!(parse_duration("1m"), Ok(Duration::from_secs(60)));
+    assert!(parse_duration("2h").is_err());
+    assert!(parse_duration("soon").is_err());
+  }
+
+  #[test]
+  fn test_resolve_shorthands() {
+    let mut cli = Cli::parse_from(["needs", "lint"]);
+    cli.resolve_shorthands();
+    assert!(matches!(cli.command, Some(Command::Lint { path: None })));
+    assert!(!cli.all);
+
+    let mut cli = Cli::parse_from(["needs", "which", "python"]);
+    cli.resolve_shorthands();
+    assert!(matches!(cli.command, Some(Command::Which { .. })));
+    assert_eq!(cli.bins, Some(vec!["python".to_string()]));
+    assert!(cli.all);
+  }
+
+  #[cfg(feature = "version-retrieval")]
+  #[test]
+  fn test_probing_flags_after_subcommand() {
+    let cli = Cli::parse_from([
+      "needs",
+      "verify",
+      "--no-cache",
+      "--probe-timeout",
+      "2s",
+      "-F",
+    ]);
+    assert!(matches!(cli.command, Some(Command::Verify { path: None })));
+    assert!(cli.no_cache && cli.full_versions);
+    assert_eq!(cli.probe_timeout, Duration::from_secs(2));
+  }
+
+  #[cfg(feature = "version-retrieval")]
+  #[test]
+  fn test_needsfile_flags_after_subcommand() {
+    let cli = Cli::parse_from(["needs", "lock", "-f", "tools/ci.needs", "--group", "build"]);
+    assert!(matches!(cli.command, Some(Command::Lock { path: None })));
+    assert_eq!(cli.files, vec![PathBuf::from("tools/ci.needs")]);
+    assert_eq!(cli.group, Some(vec!["build".to_string()]));
+  }
 }
@@ -1,8 +1,9 @@
-use crate::binary::Binary;
+use crate::binary::{Binary, Shadowed};
 use crate::error::DiscoveryError;
+use beef::Cow;
 use log::{info, warn};
 use miette::Result;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Detect which package manager is responsible for managing a binary based on its path
 fn detect_package_manager(binary_path: &Path) -> Option<String> {
@@ -94,6 +95,75 @@ fn detect_package_manager(binary_path: &Path) -> Option<String> {
   None
 }
 
+/// Look up the names of a binary in order, returning the first one found on the PATH
+fn find_first_candidate<'a>(binary: &Binary<'a>) -> Result<Option<(Cow<'a, str>, PathBuf)>> {
+  for candidate in binary.candidates() {
+    let name = candidate.as_ref();
+    match which::which(name) {
+      Ok(path) => {
+        info!(SCOPE = "which", bin = name; "found");
+        return Ok(Some((candidate, path)));
+      }
+      Err(err) => {
+        info!(SCOPE = "which", bin = name; "not found");
+        // Check if it's a permission issue or other IO error that we should report
+        if let which::Error::CannotFindBinaryPath = err {
+          continue;
+        }
+        warn!(SCOPE = "which", bin = name, error:display = err; "error during binary check");
+        return Err(
+          DiscoveryError::BinaryCheck {
+            name: name.to_string(),
+            source: std::io::Error::other(err),
+          }
+          .into(),
+        );
+      }
+    }
+  }
+  Ok(None)
+}
+
+/// The copies in `paths` other than `found`, a file reached through several links counts once
+fn shadowed_copies(found: &Path, paths: impl Iterator<Item = PathBuf>) -> Vec<Shadowed> {
+  let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  let mut seen = vec![canonical(found)];
+  paths
+    .filter_map(|path| {
+      let real = canonical(&path);
+      if seen.contains(&real) {
+        return None;
+      }
+      seen.push(real);
+      Some(Shadowed {
+        package_manager: detect_package_manager(&path),
+        path,
+        version: None,
+      })
+    })
+    .collect()
+}
+
+/// Fill in the copies that the found binaries hide, for `--all`
+pub fn find_shadowed(binaries: &mut [Binary]) {
+  for binary in binaries {
+    let Some(ref path) = binary.path else {
+      continue;
+    };
+    let shadowed = match which::which_all(binary.name.as_ref()) {
+      Ok(paths) => shadowed_copies(path, paths),
+      Err(err) => {
+        warn!(SCOPE = "which", bin = binary.name.as_ref(), error:display = err; "error looking for other copies");
+        Vec::new()
+      }
+    };
+    if !shadowed.is_empty() {
+      info!(SCOPE = "which", bin = binary.name.as_ref(), count = shadowed.len(); "found shadowed copies");
+    }
+    binary.shadowed = Some(shadowed);
+  }
+}
+
 pub fn partition_binaries(
   binaries_to_check: Vec<Binary<'_>>,
 ) -> Result<(Vec<Binary<'_>>, Vec<Binary<'_>>)> {
@@ -105,30 +175,21 @@ pub fn partition_binaries(
   let mut not_available: Vec<Binary> = Vec::new();
 
   for binary in binaries_to_check {
-    let name = binary.name.as_ref();
-    match which::which(name) {
-      Ok(path) => {
-        info!(SCOPE = "which", bin = name; "found");
+    match find_first_candidate(&binary)? {
+      Some((name, path)) => {
+        if !binary.alternatives.is_empty() {
+          info!(SCOPE = "which", bin = name.as_ref(), alternatives = binary.display_name().as_str(); "satisfied by alternative");
+        }
         let package_manager = detect_package_manager(&path);
-        let updated_binary = Binary::new_with_package_manager(binary.name, package_manager);
+        let updated_binary = Binary {
+          name,
+          package_manager,
+          path: Some(path),
+          ..binary
+        };
         available.push(updated_binary);
       }
-      Err(err) => {
-        info!(SCOPE = "which", bin = name; "not found");
-        // Check if it's a permission issue or other IO error that we should report
-        if let which::Error::CannotFindBinaryPath = err {
-          not_available.push(binary);
-        } else {
-          warn!(SCOPE = "which", bin = name, error:display = err; "error during binary check");
-          return Err(
-            DiscoveryError::BinaryCheck {
-              name: name.to_string(),
-              source: std::io::Error::other(err),
-            }
-            .into(),
-          );
-        }
-      }
+      None => not_available.push(binary),
     }
   }
   Ok((available, not_available))
@@ -228,6 +289,38 @@ mod tests {
     }
   }
 
+  #[test]
+  fn test_partition_binaries_alternatives() {
+    let cargo_exists = which::which("cargo").is_ok();
+
+    let mut binary = Binary::new(Cow::borrowed("hopefully_non_existent_binary_dsfargeg"));
+    binary.alternatives = vec![
+      Cow::borrowed("hopefully_non_existent_binary_dsfargeg"),
+      Cow::borrowed("cargo"),
+    ];
+    let mut missing = Binary::new(Cow::borrowed("non_existent_a_dsfargeg"));
+    missing.alternatives = vec![
+      Cow::borrowed("non_existent_a_dsfargeg"),
+      Cow::borrowed("non_existent_b_dsfargeg"),
+    ];
+
+    let (available, not_available) = partition_binaries(vec![binary, missing]).unwrap();
+
+    assert_eq!(not_available.len(), 1);
+    assert_eq!(
+      not_available[0].display_name(),
+      "non_existent_a_dsfargeg | non_existent_b_dsfargeg"
+    );
+    if cargo_exists {
+      assert_eq!(available.len(), 1);
+      assert_eq!(available[0].name, "cargo");
+      assert_eq!(
+        available[0].display_name(),
+        "hopefully_non_existent_binary_dsfargeg | cargo"
+      );
+    }
+  }
+
   #[test]
   fn test_partition_binaries_empty() {
     let result = partition_binaries(vec![]);
@@ -242,4 +335,30 @@ mod tests {
       assert!(err_string.contains("needs::discovery::no_binaries"));
     }
   }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_shadowed_copies() {
+    let dir = crate::test_utils::TempTree::new("shadowed");
+    for sub in ["a", "b", "c"] {
+      std::fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    let first = dir.join("a/tool");
+    let other = dir.join("c/tool");
+    std::fs::write(&first, "").unwrap();
+    std::fs::write(&other, "").unwrap();
+    // a link to the first copy doesn't shadow anything
+    std::os::unix::fs::symlink(&first, dir.join("b/tool")).unwrap();
+
+    let paths = ["a", "b", "c"].map(|sub| dir.join(sub).join("tool"));
+    let shadowed = shadowed_copies(&first, paths.into_iter());
+    assert_eq!(
+      shadowed,
+      vec![Shadowed {
+        path: other,
+        package_manager: None,
+        version: None,
+      }]
+    );
+  }
 }
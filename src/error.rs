@@ -1,5 +1,5 @@
-use std::path::PathBuf;
 use miette::{Diagnostic, NamedSource, SourceSpan};
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug, Diagnostic)]
@@ -62,6 +62,13 @@ pub enum DiscoveryError {
   )]
   NoBinariesSpecified,
 
+  #[error("Binary not found: {name}")]
+  #[diagnostic(
+    code(needs::discovery::not_found),
+    help("Install it or add its directory to the PATH.")
+  )]
+  NotFound { name: String },
+
   #[error("Failed to check if binary exists: {name}")]
   #[diagnostic(code(needs::discovery::binary_check_failed))]
   BinaryCheck {
@@ -98,6 +105,41 @@ pub enum VersionError {
     #[source]
     source: semver::Error,
   },
+
+  #[error("Version probe of {name} timed out after {timeout:?}")]
+  #[diagnostic(
+    code(needs::version::timeout),
+    help(
+      "Raise the limit with `--probe-timeout` or set a `version-cmd` that doesn't wait for input."
+    )
+  )]
+  Timeout {
+    name: String,
+    timeout: std::time::Duration,
+  },
+
+  #[error("{name} {version} doesn't meet the requirement {requirement}")]
+  #[diagnostic(
+    code(needs::version::requirement_not_met),
+    help("Update it or relax the requirement in the needsfile.")
+  )]
+  RequirementNotMet {
+    name: String,
+    requirement: String,
+    version: String,
+  },
+
+  #[error("Invalid version regex for {name}: {pattern}")]
+  #[diagnostic(
+    code(needs::version::invalid_regex),
+    help("Check the `version-regex` of the entry.")
+  )]
+  InvalidRegex {
+    name: String,
+    pattern: String,
+    #[source]
+    source: regex::Error,
+  },
 }
 
 #[derive(Error, Debug, Diagnostic)]
@@ -114,6 +156,68 @@ pub enum ValidationError {
     #[help]
     advice: Option<String>,
   },
+
+  #[error("Include cycle in '{filename}'")]
+  #[diagnostic(
+    code(needs::validation::include_cycle),
+    help("The includes form a loop: {chain}")
+  )]
+  IncludeCycle {
+    filename: String,
+    chain: String,
+    #[source_code]
+    source_code: NamedSource<String>,
+    #[label("this includes a file that is already being read")]
+    span: SourceSpan,
+  },
+
+  #[error("Broken include of '{path}'")]
+  #[diagnostic(code(needs::validation::broken_include), help("{reason}"))]
+  BrokenInclude {
+    path: String,
+    reason: String,
+    #[source_code]
+    source_code: NamedSource<String>,
+    #[label("every run fails on this include")]
+    span: SourceSpan,
+  },
+
+  #[error("Duplicate entry '{name}'")]
+  #[diagnostic(
+    code(needs::validation::duplicate_entry),
+    help("'{name}' is already listed on line {first_line}, remove one of them.")
+  )]
+  DuplicateEntry {
+    name: String,
+    first_line: usize,
+    #[source_code]
+    source_code: NamedSource<String>,
+    #[label("listed again here")]
+    span: SourceSpan,
+  },
+
+  #[error("'{name}' is a shell builtin")]
+  #[diagnostic(
+    code(needs::validation::builtin_entry),
+    help("Shell builtins and core utilities are always there and have no version to check.")
+  )]
+  BuiltinEntry {
+    name: String,
+    #[source_code]
+    source_code: NamedSource<String>,
+    #[label("builtin")]
+    span: SourceSpan,
+  },
+
+  #[error("Unreachable platform condition")]
+  #[diagnostic(code(needs::validation::unreachable_platform), help("{reason}"))]
+  UnreachablePlatform {
+    reason: String,
+    #[source_code]
+    source_code: NamedSource<String>,
+    #[label("never applies")]
+    span: SourceSpan,
+  },
 }
 
 #[derive(Error, Debug, Diagnostic)]
@@ -122,6 +226,47 @@ pub enum ConfigError {
   #[diagnostic(code(needs::config::invalid_config), help("{advice}"))]
   Invalid { reason: String, advice: String },
 
+  #[error("Invalid probe table: {message}")]
+  #[diagnostic(
+    code(needs::config::invalid_probes),
+    help("Each table needs a binary name and may set `args`, `stream`, `regex` and `builtin`.")
+  )]
+  ProbeTable {
+    message: String,
+    #[source_code]
+    source_code: NamedSource<String>,
+    #[label("here")]
+    span: SourceSpan,
+  },
+
+  #[error("Invalid lockfile: {message}")]
+  #[diagnostic(
+    code(needs::config::invalid_lockfile),
+    help("The lockfile is written by `needs lock`, run it again to replace this one.")
+  )]
+  Lockfile {
+    message: String,
+    #[source_code]
+    source_code: NamedSource<String>,
+    #[label("here")]
+    span: SourceSpan,
+  },
+
+  #[error("Invalid template: {message}")]
+  #[diagnostic(
+    code(needs::config::invalid_template),
+    help(
+      "Placeholders are {{name}}, {{version}}, {{full_version}}, {{package_manager}}, {{path}} and {{status}}, write `{{{{` and `}}}}` for braces."
+    )
+  )]
+  Template {
+    message: String,
+    #[source_code]
+    source_code: NamedSource<String>,
+    #[label("here")]
+    span: SourceSpan,
+  },
+
   #[error("Failed to set up logger")]
   #[diagnostic(code(needs::config::logger_setup_failed))]
   LoggerSetup {
@@ -0,0 +1,195 @@
+use std::path::Path;
+
+use log::info;
+use miette::{Report, Result};
+
+use crate::error::IoError;
+use crate::lint;
+use crate::parser;
+use crate::syntax::{Document, Node};
+
+/// An entry with the comments that belong to it, moved together when sorting
+struct Item {
+  /// Comment lines directly above the entry
+  leading: Vec<String>,
+  text: String,
+  key: String,
+  comment: Option<String>,
+}
+
+/// A run of entries and comments, ended by a blank line, a section header or an include
+#[derive(Default)]
+struct Paragraph {
+  /// Comment lines before the first entry, they describe the whole paragraph
+  header: Vec<String>,
+  items: Vec<Item>,
+  /// Comment lines waiting for the next entry
+  pending: Vec<String>,
+}
+
+impl Paragraph {
+  fn push_comment(&mut self, comment: String) {
+    if self.items.is_empty() {
+      self.header.push(comment);
+    } else {
+      self.pending.push(comment);
+    }
+  }
+
+  fn flush(&mut self, out: &mut Vec<String>) {
+    let Paragraph {
+      header,
+      mut items,
+      pending,
+    } = std::mem::take(self);
+    out.extend(header);
+
+    items.sort_by(|a, b| a.key.cmp(&b.key));
+    let width = items
+      .iter()
+      .filter(|item| item.comment.is_some())
+      .map(|item| item.text.len())
+      .max()
+      .unwrap_or(0);
+    for item in items {
+      out.extend(item.leading);
+      match item.comment {
+        Some(comment) => out.push(format!("{:<width$} {}", item.text, comment)),
+        None => out.push(item.text),
+      }
+    }
+    out.extend(pending);
+  }
+}
+
+/// Format a needsfile: one entry per line, entries sorted within their paragraph and inline comments aligned.
+/// `None` if some line can't be parsed, nothing is rewritten then.
+pub fn format_content(content: &str) -> Option<String> {
+  let document = Document::parse(content);
+  if document.errors().next().is_some() {
+    return None;
+  }
+
+  let mut out: Vec<String> = Vec::new();
+  let mut paragraph = Paragraph::default();
+  let blank_line = |out: &mut Vec<String>| {
+    if out.last().is_some_and(|line| !line.is_empty()) {
+      out.push(String::new());
+    }
+  };
+
+  for line in document.lines {
+    if line.is_blank() {
+      paragraph.flush(&mut out);
+      blank_line(&mut out);
+      continue;
+    }
+    if line.nodes.is_empty() {
+      paragraph.push_comment(line.comment.unwrap_or_default());
+      continue;
+    }
+
+    let mut comment = line.comment;
+    for node in line.nodes {
+      match node {
+        Node::Section { text, .. } => {
+          paragraph.flush(&mut out);
+          blank_line(&mut out);
+          out.push(with_comment(text, comment.take()));
+        }
+        Node::Include { path, .. } => {
+          paragraph.flush(&mut out);
+          out.push(with_comment(format!("include {}", path), comment.take()));
+        }
+        Node::Entry { entry, .. } => paragraph.items.push(Item {
+          leading: std::mem::take(&mut paragraph.pending),
+          text: entry.to_string(),
+          key: entry.name.to_lowercase(),
+          comment: None,
+        }),
+      }
+    }
+    // a comment after several entries stays with the last one
+    if let Some(comment) = comment {
+      match paragraph.items.last_mut() {
+        Some(item) if item.comment.is_none() => item.comment = Some(comment),
+        _ => paragraph.push_comment(comment),
+      }
+    }
+  }
+  paragraph.flush(&mut out);
+
+  while out.last().is_some_and(|line| line.is_empty()) {
+    out.pop();
+  }
+  if out.is_empty() {
+    return Some(String::new());
+  }
+  Some(out.join("\n") + "\n")
+}
+
+fn with_comment(text: String, comment: Option<String>) -> String {
+  match comment {
+    Some(comment) => format!("{} {}", text, comment),
+    None => text,
+  }
+}
+
+pub fn run(path: Option<&Path>, check: bool) -> Result<()> {
+  let path = lint::target_path(path)?;
+  let content = lint::read(&path)?;
+  let filename = path.display().to_string();
+
+  let Some(formatted) = format_content(&content) else {
+    for line in Document::parse(&content).errors() {
+      if let Some(ref err) = line.error {
+        let problem = parser::invalid_content_at(err, &filename, &content, line.offset);
+        eprintln!("{:?}", Report::new(problem));
+      }
+    }
+    eprintln!("{}: not formatted, fix the errors above first", filename);
+    std::process::exit(1);
+  };
+
+  if formatted == content {
+    info!(path:display = path.display(); "already formatted");
+    return Ok(());
+  }
+  if check {
+    eprintln!("{}: not formatted, run `needs fmt` to fix", filename);
+    std::process::exit(1);
+  }
+
+  std::fs::write(&path, formatted).map_err(|source| IoError::FileWrite {
+    path: path.clone(),
+    source,
+  })?;
+  info!(path:display = path.display(); "formatted needsfile");
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_format_sorts_and_aligns() {
+    let content = "# tools\n\nzoxide   git # vcs\n# for building\ncargo  >=1.80\n\n\n[lint]\nshellcheck # shell\nbacon? # watch\n";
+    let expected = "# tools\n\n# for building\ncargo >=1.80\ngit # vcs\nzoxide\n\n[lint]\nbacon?     # watch\nshellcheck # shell\n";
+    assert_eq!(format_content(content).unwrap(), expected);
+  }
+
+  #[test]
+  fn test_format_leading_comment_moves_with_entry() {
+    let content = "git\n# needed for docs\nmdbook\nbat\n";
+    let expected = "bat\ngit\n# needed for docs\nmdbook\n";
+    assert_eq!(format_content(content).unwrap(), expected);
+    // formatting is stable
+    assert_eq!(format_content(expected).unwrap(), expected);
+  }
+
+  #[test]
+  fn test_format_refuses_broken_file() {
+    assert!(format_content("git\nr$g\n").is_none());
+  }
+}
@@ -0,0 +1,76 @@
+use miette::Result;
+
+use super::sorted_cases;
+use crate::output::Report;
+
+const HEADER: &str = "name,status,version,package_manager,path";
+
+/// Quote fields with separators, quotes or line breaks (RFC 4180)
+fn field(text: &str) -> String {
+  if text.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", text.replace('"', "\"\""))
+  } else {
+    text.to_string()
+  }
+}
+
+/// One row per entry, sorted by name
+pub fn render(report: &Report) -> Result<String> {
+  let mut csv = format!("{}\n", HEADER);
+  for case in sorted_cases(report) {
+    let bin = case.bin;
+    let path = bin
+      .path
+      .as_ref()
+      .map(|path| path.display().to_string())
+      .unwrap_or_default();
+    let row = [
+      bin.display_name(),
+      case.status_text(),
+      case.version_text(),
+      bin.package_manager.clone().unwrap_or_default(),
+      path,
+    ];
+    let row: Vec<String> = row.iter().map(|value| field(value)).collect();
+    csv.push_str(&row.join(","));
+    csv.push('\n');
+  }
+  Ok(csv)
+}
+
+pub fn print_report(report: &Report) -> Result<()> {
+  print!("{}", render(report)?);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use beef::Cow;
+
+  use super::*;
+  use crate::binary::Binary;
+
+  #[test]
+  fn test_render_csv() {
+    let report = Report {
+      available: vec![Binary {
+        version: Some(semver::Version::new(2, 39, 5)),
+        path: Some(PathBuf::from("/opt/my, tools/git")),
+        ..Binary::new(Cow::borrowed("git"))
+      }],
+      missing_optional: vec![Binary {
+        optional: true,
+        ..Binary::new(Cow::borrowed("bat"))
+      }],
+      ..Report::default()
+    };
+    assert_eq!(
+      render(&report).unwrap(),
+      "name,status,version,package_manager,path\n\
+       bat,missing (optional),,,\n\
+       git,found,2.39.5,,\"/opt/my, tools/git\"\n"
+    );
+  }
+}
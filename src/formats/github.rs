@@ -0,0 +1,192 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use log::{debug, info};
+use miette::Result;
+
+use super::markdown::cell;
+use super::{Case, Outcome, Status, cases};
+use crate::error::IoError;
+use crate::output::Report;
+
+/// Set by GitHub Actions for every step
+pub fn is_github_actions() -> bool {
+  std::env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true")
+}
+
+/// Messages of workflow commands escape `%` and line breaks
+fn escape_data(text: &str) -> String {
+  text
+    .replace('%', "%25")
+    .replace('\r', "%0D")
+    .replace('\n', "%0A")
+}
+
+/// Properties like `file=` also escape `:` and `,`
+fn escape_property(text: &str) -> String {
+  escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Annotations want paths relative to the checkout, which is the working directory of a step
+fn relative(file: &Path) -> PathBuf {
+  std::env::current_dir()
+    .ok()
+    .and_then(|cwd| file.strip_prefix(cwd).ok().map(Path::to_path_buf))
+    .unwrap_or_else(|| file.to_path_buf())
+}
+
+/// `::error file=needsfile,line=3::...` for failures, a warning for skipped optional binaries
+fn annotation(case: &Case) -> Option<String> {
+  let (level, message) = match case.outcome {
+    Outcome::Passed => return None,
+    Outcome::Failed(ref failure) => ("error", failure.to_string()),
+    Outcome::Skipped(_) if case.status == Status::Missing => (
+      "warning",
+      format!("Optional binary not found: {}", case.bin.display_name()),
+    ),
+    Outcome::Skipped(ref reason) => ("warning", reason.clone()),
+  };
+  let mut properties = Vec::new();
+  if let Some(ref file) = case.bin.file {
+    properties.push(format!(
+      "file={}",
+      escape_property(&relative(file).display().to_string())
+    ));
+  }
+  if let Some(line) = case.bin.line {
+    properties.push(format!("line={}", line));
+  }
+  properties.push(format!(
+    "title={}",
+    escape_property(&case.code().unwrap_or_else(|| "needs".to_string()))
+  ));
+  Some(format!(
+    "::{} {}::{}",
+    level,
+    properties.join(","),
+    escape_data(&message)
+  ))
+}
+
+pub fn annotations(report: &Report) -> Vec<String> {
+  cases(report).iter().filter_map(annotation).collect()
+}
+
+/// A Markdown table of every entry for the step summary
+pub fn summary(report: &Report) -> String {
+  let mut table = String::from(
+    "### needs\n\n| Status | Binary | Version | Requirement | Path |\n| --- | --- | --- | --- | --- |\n",
+  );
+  for case in cases(report) {
+    let bin = case.bin;
+    let requirement = bin
+      .requirement
+      .as_ref()
+      .map(ToString::to_string)
+      .unwrap_or_default();
+    let path = bin
+      .path
+      .as_ref()
+      .map(|path| format!("`{}`", path.display()))
+      .unwrap_or_default();
+    table.push_str(&format!(
+      "| {} | {} | {} | {} | {} |\n",
+      case.status_text(),
+      cell(&bin.display_name()),
+      cell(&case.version_text()),
+      cell(&requirement),
+      cell(&path)
+    ));
+  }
+  table
+}
+
+/// Append the summary to the file GitHub shows on the run page
+pub fn write_summary(report: &Report, path: &Path) -> Result<()> {
+  let write_err = |source| IoError::FileWrite {
+    path: path.to_path_buf(),
+    source,
+  };
+  let mut file = std::fs::OpenOptions::new()
+    .append(true)
+    .create(true)
+    .open(path)
+    .map_err(write_err)?;
+  writeln!(file, "{}", summary(report)).map_err(write_err)?;
+  info!(path:display = path.display(); "wrote step summary");
+  Ok(())
+}
+
+/// Print the annotations and write the step summary if `GITHUB_STEP_SUMMARY` is set
+pub fn print_report(report: &Report) -> Result<()> {
+  for annotation in annotations(report) {
+    println!("{}", annotation);
+  }
+  match std::env::var_os("GITHUB_STEP_SUMMARY").filter(|path| !path.is_empty()) {
+    Some(path) => write_summary(report, Path::new(&path)),
+    None => {
+      debug!("GITHUB_STEP_SUMMARY not set, no step summary");
+      Ok(())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use beef::Cow;
+  use semver::{Version as SemVersion, VersionReq};
+
+  use super::*;
+  use crate::binary::Binary;
+  use crate::test_utils::TempTree;
+
+  fn report() -> Report<'static> {
+    Report {
+      available: vec![Binary {
+        version: Some(SemVersion::new(2, 39, 5)),
+        path: Some(PathBuf::from("/usr/bin/git")),
+        ..Binary::new(Cow::borrowed("git"))
+      }],
+      outdated: vec![Binary {
+        requirement: Some(VersionReq::parse(">=20").unwrap()),
+        version: Some(SemVersion::new(18, 1, 0)),
+        file: Some(PathBuf::from("ci/needsfile")),
+        line: Some(4),
+        ..Binary::new(Cow::borrowed("node"))
+      }],
+      not_available: vec![Binary::new(Cow::borrowed("rg"))],
+      missing_optional: vec![Binary {
+        optional: true,
+        file: Some(PathBuf::from("needsfile")),
+        line: Some(7),
+        ..Binary::new(Cow::borrowed("extra"))
+      }],
+    }
+  }
+
+  #[test]
+  fn test_annotations() {
+    assert_eq!(
+      annotations(&report()),
+      vec![
+        "::error file=ci/needsfile,line=4,title=needs%3A%3Aversion%3A%3Arequirement_not_met::node 18.1.0 doesn't meet the requirement >=20",
+        "::error title=needs%3A%3Adiscovery%3A%3Anot_found::Binary not found: rg",
+        "::warning file=needsfile,line=7,title=needs::Optional binary not found: extra",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_write_summary() {
+    let dir = TempTree::new("summary");
+    let path = dir.join("summary.md");
+    std::fs::write(&path, "earlier step\n").unwrap();
+
+    write_summary(&report(), &path).unwrap();
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.starts_with("earlier step\n### needs\n"));
+    assert!(content.contains("| found | git | 2.39.5 |  | `/usr/bin/git` |"));
+    assert!(content.contains("| outdated | node | 18.1.0 | >=20 |  |"));
+    assert!(content.contains("| missing (optional) | extra |  |  |  |"));
+  }
+}
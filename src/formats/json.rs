@@ -0,0 +1,209 @@
+use miette::Result;
+use serde::Serialize;
+
+use crate::binary::Binary;
+use crate::output::Report;
+use crate::parser::Stream;
+
+/// Bumped whenever a field is removed or changes its meaning, added fields keep the version
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+struct Document<'a> {
+  schema_version: u32,
+  needs_version: &'static str,
+  /// `false` if a required binary is missing or outdated
+  ok: bool,
+  found: Vec<JsonBinary<'a>>,
+  outdated: Vec<JsonBinary<'a>>,
+  missing: Vec<JsonBinary<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonBinary<'a> {
+  name: &'a str,
+  alternatives: Vec<&'a str>,
+  optional: bool,
+  group: Option<&'a str>,
+  /// Lossy, so that a path that isn't UTF-8 can't break the document
+  path: Option<String>,
+  package_manager: Option<&'a str>,
+  requirement: Option<String>,
+  version: Option<JsonVersion<'a>>,
+  probe: Option<JsonProbe<'a>>,
+  /// Copies further down the PATH, only with `--all`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  shadowed: Option<Vec<JsonShadowed<'a>>>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonShadowed<'a> {
+  path: String,
+  package_manager: Option<&'a str>,
+  version: Option<String>,
+  /// Has a higher version than the copy that is used
+  newer: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonVersion<'a> {
+  /// As the binary printed it, `null` if it came from an older cache
+  raw: Option<&'a str>,
+  semver: String,
+  major: u64,
+  minor: u64,
+  patch: u64,
+  pre: String,
+  build: String,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonProbe<'a> {
+  args: &'a [String],
+  stream: Option<Stream>,
+  duration_ms: f64,
+  cached: bool,
+  timed_out: bool,
+}
+
+impl<'a> JsonBinary<'a> {
+  fn new(bin: &'a Binary) -> Self {
+    let raw = bin
+      .probed
+      .as_ref()
+      .and_then(|probed| probed.raw_version.as_deref())
+      .filter(|raw| !raw.is_empty());
+    Self {
+      name: &bin.name,
+      alternatives: bin.alternatives.iter().map(|name| name.as_ref()).collect(),
+      optional: bin.optional,
+      group: bin.group.as_deref(),
+      path: bin
+        .path
+        .as_ref()
+        .map(|path| path.to_string_lossy().into_owned()),
+      package_manager: bin.package_manager.as_deref(),
+      requirement: bin.requirement.as_ref().map(ToString::to_string),
+      version: bin.version.as_ref().map(|version| JsonVersion {
+        raw,
+        semver: version.to_string(),
+        major: version.major,
+        minor: version.minor,
+        patch: version.patch,
+        pre: version.pre.to_string(),
+        build: version.build.to_string(),
+      }),
+      probe: bin.probed.as_ref().map(|probed| JsonProbe {
+        args: &probed.args,
+        stream: probed.stream,
+        duration_ms: probed.duration.as_secs_f64() * 1000.0,
+        cached: probed.cached,
+        timed_out: bin.timed_out,
+      }),
+      shadowed: bin.shadowed.as_ref().map(|shadowed| {
+        shadowed
+          .iter()
+          .map(|copy| JsonShadowed {
+            path: copy.path.to_string_lossy().into_owned(),
+            package_manager: copy.package_manager.as_deref(),
+            version: copy.version.as_ref().map(ToString::to_string),
+            newer: bin.is_newer(copy),
+          })
+          .collect()
+      }),
+    }
+  }
+}
+
+/// The whole report as one pretty printed JSON document
+pub fn render(report: &Report) -> Result<String> {
+  let document = Document {
+    schema_version: SCHEMA_VERSION,
+    needs_version: env!("CARGO_PKG_VERSION"),
+    ok: report.not_available.is_empty() && report.outdated.iter().all(|bin| bin.optional),
+    found: report.available.iter().map(JsonBinary::new).collect(),
+    outdated: report.outdated.iter().map(JsonBinary::new).collect(),
+    missing: report
+      .not_available
+      .iter()
+      .chain(&report.missing_optional)
+      .map(JsonBinary::new)
+      .collect(),
+  };
+  Ok(
+    serde_json::to_string_pretty(&document)
+      .expect("the document only holds strings, numbers and booleans"),
+  )
+}
+
+pub fn print_report(report: &Report) -> Result<()> {
+  println!("{}", render(report)?);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+  use std::time::Duration;
+
+  use beef::Cow;
+  use semver::Version as SemVersion;
+
+  use super::*;
+  use crate::binary::Probed;
+
+  #[test]
+  fn test_render_json() {
+    let report = Report {
+      available: vec![Binary {
+        version: Some(SemVersion::parse("1.2.3-beta").unwrap()),
+        path: Some(PathBuf::from("/usr/bin/tool")),
+        probed: Some(Probed {
+          args: vec!["-V".to_string()],
+          stream: Some(Stream::Stderr),
+          raw_version: Some("v1.2.3-beta".to_string()),
+          duration: Duration::from_millis(12),
+          cached: false,
+        }),
+        ..Binary::new(Cow::borrowed("tool"))
+      }],
+      missing_optional: vec![Binary {
+        optional: true,
+        ..Binary::new(Cow::borrowed("extra"))
+      }],
+      ..Report::default()
+    };
+    let json: serde_json::Value = serde_json::from_str(&render(&report).unwrap()).unwrap();
+    assert_eq!(json["schema_version"], SCHEMA_VERSION);
+    assert_eq!(json["ok"], true);
+
+    let tool = &json["found"][0];
+    assert_eq!(tool["path"], "/usr/bin/tool");
+    assert_eq!(tool["version"]["raw"], "v1.2.3-beta");
+    assert_eq!(tool["version"]["semver"], "1.2.3-beta");
+    assert_eq!(tool["version"]["pre"], "beta");
+    assert_eq!(tool["probe"]["args"][0], "-V");
+    assert_eq!(tool["probe"]["stream"], "stderr");
+    assert_eq!(tool["probe"]["duration_ms"], 12.0);
+
+    assert_eq!(json["missing"][0]["name"], "extra");
+    assert_eq!(json["missing"][0]["version"], serde_json::Value::Null);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_render_json_non_utf8_path() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let report = Report {
+      available: vec![Binary {
+        path: Some(PathBuf::from(OsStr::from_bytes(b"/usr/bin/t\xffol"))),
+        ..Binary::new(Cow::borrowed("tool"))
+      }],
+      ..Report::default()
+    };
+    let json: serde_json::Value = serde_json::from_str(&render(&report).unwrap()).unwrap();
+    assert_eq!(json["found"][0]["path"], "/usr/bin/t\u{fffd}ol");
+  }
+}
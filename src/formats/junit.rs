@@ -0,0 +1,138 @@
+use miette::Result;
+
+use super::{Case, Outcome, cases};
+use crate::output::Report;
+
+/// Escape text for XML attributes and content
+fn escape(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+fn count(cases: &[&Case], matches: fn(&Outcome) -> bool) -> usize {
+  cases.iter().filter(|case| matches(&case.outcome)).count()
+}
+
+/// One `<testsuite>` per needsfile section, ungrouped entries go into `needs`
+pub fn render(report: &Report) -> Result<String> {
+  let cases = cases(report);
+  let mut suites: Vec<(String, Vec<&Case>)> = Vec::new();
+  for case in &cases {
+    let suite = match case.bin.group {
+      Some(ref group) => format!("needs.{}", group),
+      None => "needs".to_string(),
+    };
+    match suites.iter_mut().find(|(name, _)| *name == suite) {
+      Some((_, cases)) => cases.push(case),
+      None => suites.push((suite, vec![case])),
+    }
+  }
+
+  let failed = |outcome: &Outcome| matches!(outcome, Outcome::Failed(_));
+  let skipped = |outcome: &Outcome| matches!(outcome, Outcome::Skipped(_));
+  let all: Vec<&Case> = cases.iter().collect();
+  let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  xml.push_str(&format!(
+    "<testsuites name=\"needs\" tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n",
+    all.len(),
+    count(&all, failed),
+    count(&all, skipped)
+  ));
+  for (suite, cases) in &suites {
+    let time: f64 = cases.iter().map(|case| case.duration().as_secs_f64()).sum();
+    xml.push_str(&format!(
+      "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+      escape(suite),
+      cases.len(),
+      count(cases, failed),
+      count(cases, skipped),
+      time
+    ));
+    for case in cases {
+      xml.push_str(&format!(
+        "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+        escape(&case.bin.display_name()),
+        escape(suite),
+        case.duration().as_secs_f64()
+      ));
+      match case.outcome {
+        Outcome::Passed => xml.push_str("/>\n"),
+        Outcome::Failed(ref failure) => {
+          let message = escape(&failure.to_string());
+          let help = failure
+            .help()
+            .map(|help| escape(&help.to_string()))
+            .unwrap_or_default();
+          xml.push_str(&format!(
+            ">\n      <failure message=\"{}\" type=\"{}\">{}</failure>\n    </testcase>\n",
+            message,
+            case.code().unwrap_or_default(),
+            help
+          ));
+        }
+        Outcome::Skipped(ref message) => {
+          xml.push_str(&format!(
+            ">\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+            escape(message)
+          ));
+        }
+      }
+    }
+    xml.push_str("  </testsuite>\n");
+  }
+  xml.push_str("</testsuites>");
+  Ok(xml)
+}
+
+pub fn print_report(report: &Report) -> Result<()> {
+  println!("{}", render(report)?);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use beef::Cow;
+  use semver::VersionReq;
+
+  use super::*;
+  use crate::binary::Binary;
+
+  #[test]
+  fn test_render_junit() {
+    let report = Report {
+      available: vec![Binary::new(Cow::borrowed("git"))],
+      outdated: vec![Binary {
+        requirement: Some(VersionReq::parse(">=20").unwrap()),
+        version: Some(semver::Version::new(18, 1, 0)),
+        group: Some("build".to_string()),
+        ..Binary::new(Cow::borrowed("node"))
+      }],
+      not_available: vec![Binary::new(Cow::borrowed("a&b"))],
+      missing_optional: vec![Binary {
+        optional: true,
+        ..Binary::new(Cow::borrowed("extra"))
+      }],
+    };
+    let xml = render(&report).unwrap();
+    assert!(xml.contains("<testsuites name=\"needs\" tests=\"4\" failures=\"2\" skipped=\"1\">"));
+    assert!(xml.contains("<testsuite name=\"needs.build\" tests=\"1\" failures=\"1\""));
+    assert!(xml.contains("<testcase name=\"git\" classname=\"needs\" time=\"0.000\"/>"));
+    assert!(xml.contains(
+      "<failure message=\"node 18.1.0 doesn&apos;t meet the requirement &gt;=20\" type=\"needs::version::requirement_not_met\">"
+    ));
+    assert!(xml.contains(
+      "<failure message=\"Binary not found: a&amp;b\" type=\"needs::discovery::not_found\">"
+    ));
+    assert!(xml.contains("<skipped message=\"optional, not found\"/>"));
+  }
+}
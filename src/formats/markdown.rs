@@ -0,0 +1,72 @@
+use miette::Result;
+
+use super::sorted_cases;
+use crate::output::Report;
+
+/// Escape the column separator inside a table cell
+pub fn cell(text: &str) -> String {
+  text.replace('|', "\\|")
+}
+
+/// One table row per entry, sorted by name
+pub fn render(report: &Report) -> Result<String> {
+  let mut table = String::from(
+    "| Name | Status | Version | Package manager | Path |\n| --- | --- | --- | --- | --- |\n",
+  );
+  for case in sorted_cases(report) {
+    let bin = case.bin;
+    let path = bin
+      .path
+      .as_ref()
+      .map(|path| format!("`{}`", path.display()))
+      .unwrap_or_default();
+    table.push_str(&format!(
+      "| {} | {} | {} | {} | {} |\n",
+      cell(&bin.display_name()),
+      case.status_text(),
+      cell(&case.version_text()),
+      cell(bin.package_manager.as_deref().unwrap_or_default()),
+      cell(&path)
+    ));
+  }
+  Ok(table)
+}
+
+pub fn print_report(report: &Report) -> Result<()> {
+  print!("{}", render(report)?);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use beef::Cow;
+
+  use super::*;
+  use crate::binary::Binary;
+
+  #[test]
+  fn test_render_markdown() {
+    let report = Report {
+      available: vec![Binary {
+        version: Some(semver::Version::new(14, 1, 1)),
+        package_manager: Some("cargo".to_string()),
+        path: Some(PathBuf::from("/home/me/.cargo/bin/rg")),
+        ..Binary::new(Cow::borrowed("rg"))
+      }],
+      not_available: vec![Binary {
+        alternatives: vec![Cow::borrowed("fd"), Cow::borrowed("fdfind")],
+        ..Binary::new(Cow::borrowed("fd"))
+      }],
+      ..Report::default()
+    };
+    assert_eq!(
+      render(&report).unwrap(),
+      "| Name | Status | Version | Package manager | Path |\n\
+       | --- | --- | --- | --- | --- |\n\
+       | fd \\| fdfind | missing |  |  |  |\n\
+       | rg | found | 14.1.1 | cargo | `/home/me/.cargo/bin/rg` |\n"
+    );
+  }
+}
@@ -0,0 +1,183 @@
+//! Machine readable reports, selected with `--format`
+
+pub mod csv;
+pub mod github;
+pub mod json;
+pub mod junit;
+pub mod markdown;
+pub mod tap;
+pub mod template;
+
+use std::time::Duration;
+
+use clap::ValueEnum;
+use miette::Report as Failure;
+
+use crate::binary::{Binary, compare_binaries};
+use crate::error::{DiscoveryError, VersionError};
+use crate::output::Report;
+use crate::versions::format_version;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+  /// the aligned, colored report
+  #[default]
+  Human,
+  /// one JSON document, see the README for the schema
+  Json,
+  /// JUnit XML, one test case per needsfile entry
+  Junit,
+  /// Test Anything Protocol, one test per needsfile entry
+  Tap,
+  /// GitHub Actions annotations and a step summary, the default when `GITHUB_ACTIONS=true`
+  Github,
+  /// a Markdown table of name, status, version, package manager and path
+  Markdown,
+  /// the columns of the Markdown table as CSV
+  Csv,
+}
+
+/// Which section of the report an entry is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+  Found,
+  Outdated,
+  Missing,
+}
+
+impl std::fmt::Display for Status {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Status::Found => write!(f, "found"),
+      Status::Outdated => write!(f, "outdated"),
+      Status::Missing => write!(f, "missing"),
+    }
+  }
+}
+
+/// What became of a needsfile entry, for the formats that report entries as tests
+#[derive(Debug)]
+pub enum Outcome {
+  Passed,
+  Failed(Failure),
+  /// optional binaries that are missing or outdated
+  Skipped(String),
+}
+
+/// A needsfile entry as a test case
+#[derive(Debug)]
+pub struct Case<'r, 'a> {
+  pub bin: &'r Binary<'a>,
+  pub status: Status,
+  pub outcome: Outcome,
+}
+
+impl Case<'_, '_> {
+  /// `git 2.39.5`, or just the name if there's no version
+  pub fn description(&self) -> String {
+    match self.bin.version {
+      Some(ref version) => format!(
+        "{} {}",
+        self.bin.display_name(),
+        format_version(version, true)
+      ),
+      None => self.bin.display_name(),
+    }
+  }
+
+  /// `missing (optional)` for skipped entries, the plain status otherwise
+  pub fn status_text(&self) -> String {
+    match self.outcome {
+      Outcome::Skipped(_) => format!("{} (optional)", self.status),
+      _ => self.status.to_string(),
+    }
+  }
+
+  /// The full version, `timed out` or an empty string
+  pub fn version_text(&self) -> String {
+    match self.bin.version {
+      Some(ref version) => format_version(version, true).to_string(),
+      None if self.bin.timed_out => "timed out".to_string(),
+      None => String::new(),
+    }
+  }
+
+  pub fn duration(&self) -> Duration {
+    self
+      .bin
+      .probed
+      .as_ref()
+      .map_or(Duration::ZERO, |probed| probed.duration)
+  }
+
+  /// The diagnostic code of a failure, e.g. `needs::discovery::not_found`
+  pub fn code(&self) -> Option<String> {
+    match self.outcome {
+      Outcome::Failed(ref failure) => failure.code().map(|code| code.to_string()),
+      _ => None,
+    }
+  }
+}
+
+fn requirement_not_met(bin: &Binary) -> Failure {
+  let version = match bin.version {
+    Some(ref version) => format_version(version, true).to_string(),
+    None if bin.timed_out => "(timed out)".to_string(),
+    None => "(unknown version)".to_string(),
+  };
+  VersionError::RequirementNotMet {
+    name: bin.display_name(),
+    requirement: bin
+      .requirement
+      .as_ref()
+      .map(ToString::to_string)
+      .unwrap_or_default(),
+    version,
+  }
+  .into()
+}
+
+/// Every entry of the report by name, whatever section it is in
+pub fn sorted_cases<'r, 'a>(report: &'r Report<'a>) -> Vec<Case<'r, 'a>> {
+  let mut cases = cases(report);
+  cases.sort_by(|a, b| compare_binaries(a.bin, b.bin));
+  cases
+}
+
+/// Every entry of the report as a test case, in the order of the report
+pub fn cases<'r, 'a>(report: &'r Report<'a>) -> Vec<Case<'r, 'a>> {
+  let passed = report.available.iter().map(|bin| Case {
+    bin,
+    status: Status::Found,
+    outcome: Outcome::Passed,
+  });
+  let outdated = report.outdated.iter().map(|bin| Case {
+    bin,
+    status: Status::Outdated,
+    outcome: if bin.optional {
+      Outcome::Skipped(requirement_not_met(bin).to_string())
+    } else {
+      Outcome::Failed(requirement_not_met(bin))
+    },
+  });
+  let missing = report.not_available.iter().map(|bin| Case {
+    bin,
+    status: Status::Missing,
+    outcome: Outcome::Failed(
+      DiscoveryError::NotFound {
+        name: bin.display_name(),
+      }
+      .into(),
+    ),
+  });
+  let missing_optional = report.missing_optional.iter().map(|bin| Case {
+    bin,
+    status: Status::Missing,
+    outcome: Outcome::Skipped("optional, not found".to_string()),
+  });
+  passed
+    .chain(outdated)
+    .chain(missing)
+    .chain(missing_optional)
+    .collect()
+}
@@ -0,0 +1,72 @@
+use miette::Result;
+
+use super::{Outcome, cases};
+use crate::output::Report;
+
+/// TAP version 13, failures get a YAML block with the message and the diagnostic code
+pub fn render(report: &Report) -> Result<String> {
+  let cases = cases(report);
+  let mut tap = format!("TAP version 13\n1..{}\n", cases.len());
+  for (number, case) in cases.iter().enumerate() {
+    let number = number + 1;
+    match case.outcome {
+      Outcome::Passed => {
+        tap.push_str(&format!("ok {} - {}\n", number, case.description()));
+      }
+      Outcome::Failed(ref failure) => {
+        tap.push_str(&format!("not ok {} - {}\n", number, case.description()));
+        tap.push_str("  ---\n");
+        tap.push_str(&format!("  message: {:?}\n", failure.to_string()));
+        if let Some(code) = case.code() {
+          tap.push_str(&format!("  code: {}\n", code));
+        }
+        if let Some(help) = failure.help() {
+          tap.push_str(&format!("  help: {:?}\n", help.to_string()));
+        }
+        tap.push_str("  ...\n");
+      }
+      Outcome::Skipped(ref reason) => {
+        tap.push_str(&format!(
+          "ok {} - {} # SKIP {}\n",
+          number,
+          case.description(),
+          reason
+        ));
+      }
+    }
+  }
+  Ok(tap)
+}
+
+pub fn print_report(report: &Report) -> Result<()> {
+  print!("{}", render(report)?);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use beef::Cow;
+
+  use super::*;
+  use crate::binary::Binary;
+
+  #[test]
+  fn test_render_tap() {
+    let report = Report {
+      available: vec![Binary {
+        version: Some(semver::Version::new(2, 39, 5)),
+        ..Binary::new(Cow::borrowed("git"))
+      }],
+      not_available: vec![Binary::new(Cow::borrowed("rg"))],
+      missing_optional: vec![Binary {
+        optional: true,
+        ..Binary::new(Cow::borrowed("extra"))
+      }],
+      ..Report::default()
+    };
+    assert_eq!(
+      render(&report).unwrap(),
+      "TAP version 13\n1..3\nok 1 - git 2.39.5\nnot ok 2 - rg\n  ---\n  message: \"Binary not found: rg\"\n  code: needs::discovery::not_found\n  help: \"Install it or add its directory to the PATH.\"\n  ...\nok 3 - extra # SKIP optional, not found\n"
+    );
+  }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use log::{debug, info};
+use miette::{NamedSource, Result};
+
+use super::{Case, sorted_cases};
+use crate::error::{ConfigError, IoError};
+use crate::output::Report;
+use crate::versions::format_version;
+
+/// What a `{placeholder}` stands for
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+  Name,
+  Version,
+  FullVersion,
+  PackageManager,
+  Path,
+  Status,
+}
+
+impl Field {
+  fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "name" => Some(Field::Name),
+      "version" => Some(Field::Version),
+      "full_version" => Some(Field::FullVersion),
+      "package_manager" => Some(Field::PackageManager),
+      "path" => Some(Field::Path),
+      "status" => Some(Field::Status),
+      _ => None,
+    }
+  }
+
+  fn value(self, case: &Case) -> String {
+    let bin = case.bin;
+    match self {
+      Field::Name => bin.display_name(),
+      Field::Version => bin
+        .version
+        .as_ref()
+        .map(|version| format_version(version, false).to_string())
+        .unwrap_or_default(),
+      Field::FullVersion => case.version_text(),
+      Field::PackageManager => bin.package_manager.clone().unwrap_or_default(),
+      Field::Path => bin
+        .path
+        .as_ref()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default(),
+      Field::Status => case.status_text(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+  Text(String),
+  Field(Field),
+}
+
+/// A line per binary from `--template`, e.g. `{name}\t{version}\t{path}`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+  pieces: Vec<Piece>,
+}
+
+impl Template {
+  /// `\t`, `\n` and `\\` are unescaped, `{{` and `}}` are literal braces
+  pub fn parse(filename: &str, template: &str) -> Result<Self> {
+    let error = |message: String, start: usize, end: usize| ConfigError::Template {
+      message,
+      source_code: NamedSource::new(filename, template.to_string()),
+      span: (start..end).into(),
+    };
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+      match c {
+        '{' if chars.next_if(|&(_, c)| c == '{').is_some() => text.push('{'),
+        '}' if chars.next_if(|&(_, c)| c == '}').is_some() => text.push('}'),
+        '{' => {
+          let Some(end) = template[start..].find('}').map(|end| start + end) else {
+            return Err(error("unclosed `{`".to_string(), start, template.len()).into());
+          };
+          let name = &template[start + 1..end];
+          let field = Field::from_name(name.trim()).ok_or_else(|| {
+            error(
+              format!("unknown placeholder `{{{}}}`", name),
+              start,
+              end + 1,
+            )
+          })?;
+          if !text.is_empty() {
+            pieces.push(Piece::Text(std::mem::take(&mut text)));
+          }
+          pieces.push(Piece::Field(field));
+          while chars.next_if(|&(index, _)| index <= end).is_some() {}
+        }
+        '}' => return Err(error("unmatched `}`".to_string(), start, start + 1).into()),
+        '\\' => match chars.next_if(|&(_, c)| matches!(c, 't' | 'n' | '\\')) {
+          Some((_, 't')) => text.push('\t'),
+          Some((_, 'n')) => text.push('\n'),
+          Some(_) => text.push('\\'),
+          None => text.push('\\'),
+        },
+        c => text.push(c),
+      }
+    }
+    if !text.is_empty() {
+      pieces.push(Piece::Text(text));
+    }
+    Ok(Self { pieces })
+  }
+
+  pub fn render(&self, case: &Case) -> String {
+    self
+      .pieces
+      .iter()
+      .map(|piece| match piece {
+        Piece::Text(text) => text.clone(),
+        Piece::Field(field) => field.value(case),
+      })
+      .collect()
+  }
+}
+
+/// Named templates, `$XDG_CONFIG_HOME/needs/templates.toml` or `~/.config/needs/templates.toml`
+pub fn templates_path() -> Option<PathBuf> {
+  crate::io::config_dir().map(|dir| dir.join("templates.toml"))
+}
+
+/// The template of `--template`, a value without placeholders or escapes names one from `templates.toml`
+pub fn load(value: &str) -> Result<Template> {
+  if value.contains(['{', '}', '\\']) {
+    return Template::parse("--template", value);
+  }
+  let unknown = |path: &str| ConfigError::Invalid {
+    reason: format!("no template named '{}'", value),
+    advice: format!(
+      "Define it in {} or use placeholders, e.g. `--template '{{name}} {{version}}'`.",
+      path
+    ),
+  };
+  let Some(path) = templates_path() else {
+    return Err(unknown("templates.toml").into());
+  };
+  if !path.is_file() {
+    debug!(path:display = path.display(); "no templates.toml");
+    return Err(unknown(&path.display().to_string()).into());
+  }
+  let content = std::fs::read_to_string(&path).map_err(|source| IoError::FileRead {
+    path: path.clone(),
+    source,
+  })?;
+  let templates: HashMap<String, String> =
+    toml::from_str(&content).map_err(|err| ConfigError::Template {
+      message: err.message().to_string(),
+      source_code: NamedSource::new(path.display().to_string(), content.clone()),
+      span: err.span().unwrap_or(0..0).into(),
+    })?;
+  let Some(template) = templates.get(value) else {
+    return Err(unknown(&path.display().to_string()).into());
+  };
+  info!(name = value, path:display = path.display(); "using named template");
+  Template::parse(&format!("{} ({})", value, path.display()), template)
+}
+
+/// One rendered line per binary, sorted by name
+pub fn print_report(report: &Report, template: &Template) -> Result<()> {
+  for case in sorted_cases(report) {
+    println!("{}", template.render(&case));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use beef::Cow;
+
+  use super::*;
+  use crate::binary::Binary;
+  use crate::formats::cases;
+
+  #[test]
+  fn test_render_template() {
+    let report = Report {
+      available: vec![Binary {
+        version: Some(semver::Version::parse("1.2.3-beta").unwrap()),
+        package_manager: Some("cargo".to_string()),
+        path: Some(PathBuf::from("/bin/rg")),
+        ..Binary::new(Cow::borrowed("rg"))
+      }],
+      not_available: vec![Binary::new(Cow::borrowed("fd"))],
+      ..Report::default()
+    };
+    let cases = cases(&report);
+    let template = Template::parse(
+      "--template",
+      r"{name}\t{ version }\t{full_version} {{{package_manager}}} {path} {status}\\",
+    )
+    .unwrap();
+    assert_eq!(
+      template.render(&cases[0]),
+      "rg\t1.2.3\t1.2.3-beta {cargo} /bin/rg found\\"
+    );
+    assert_eq!(template.render(&cases[1]), "fd\t\t {}  missing\\");
+  }
+
+  #[test]
+  fn test_template_errors() {
+    let span = |template: &str| {
+      let err = Template::parse("--template", template).unwrap_err();
+      let err = err.downcast_ref::<ConfigError>().unwrap();
+      match err {
+        ConfigError::Template { span, .. } => (span.offset(), span.len()),
+        _ => unreachable!(),
+      }
+    };
+    assert_eq!(span("{name} {vesion}"), (7, 8));
+    assert_eq!(span("{name"), (0, 5));
+    assert_eq!(span("name}"), (4, 1));
+  }
+}
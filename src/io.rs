@@ -1,67 +1,346 @@
 use crate::cli::Cli;
-use crate::error::IoError;
+use crate::error::{ConfigError, IoError, ValidationError};
 
 use crate::binary::Binary;
+use crate::parser::{self, Entry, Include, Layer, Needsfile, ProbeOverride};
+use crate::platform::{self, Platform};
 use beef::Cow;
-use log::{debug, error, warn};
-use miette::Result;
-use std::path::PathBuf;
+use log::{debug, error, info, warn};
+use miette::{NamedSource, Result};
+use std::path::{Path, PathBuf};
+
+const NEEDSFILE_NAMES: [&str; 4] = ["needsfile", ".needsfile", "needs", ".needs"];
+
+/// The directories to look for a needsfile in, from `start` up to the repository or filesystem root
+fn search_dirs(start: &Path) -> Vec<PathBuf> {
+  let mut dirs = Vec::new();
+  for dir in start.ancestors() {
+    dirs.push(dir.to_path_buf());
+    if dir.join(".git").exists() {
+      debug!(dir:display = dir.display(); "reached repository root");
+      break;
+    }
+  }
+  dirs
+}
+
+/// The path of the needsfile that would be used from `start`, without reading it
+pub fn locate_needsfile(start: &Path) -> Option<PathBuf> {
+  search_dirs(start)
+    .into_iter()
+    .flat_map(|dir| NEEDSFILE_NAMES.map(|name| dir.join(name)))
+    .find(|path| path.is_file())
+}
+
+/// Walk up from `start` like git does for `.git` and parse the first usable needsfile
+pub fn find_needsfile(start: &Path) -> Result<Option<(PathBuf, Vec<Entry>)>> {
+  for dir in search_dirs(start) {
+    for name in NEEDSFILE_NAMES {
+      let path = dir.join(name);
+      if !path.is_file() {
+        continue;
+      }
+      // Attempt to read from the first successful file path
+      match std::fs::read_to_string(&path) {
+        Ok(content) => {
+          if content.trim().is_empty() {
+            warn!(path:display = path.display(); "needsfile found but it is empty, trying next.");
+            continue; // Try next file if this one is empty
+          }
+          let needsfile = crate::parser::parse(&content).map_err(|e| {
+            debug!(path:display = path.display(), error:display = e; "Failed to parse needsfile");
+            parser::invalid_content(&e, &path.display().to_string(), &content)
+          })?;
+          if needsfile.is_empty() {
+            warn!(path:display = path.display(); "needsfile found but it is empty, trying next.");
+            continue; // Try next file if this one is empty
+          }
+
+          let entries = resolve_includes(&path, &content, needsfile, &mut Vec::new())?;
+          debug!(path:display = path.display(), binaries:debug = &entries; "found needsfile");
+          return Ok(Some((path, entries)));
+        }
+        Err(err) => {
+          debug!(path:display = path.display(), error:display = err; "Failed to read or find needsfile, trying next.");
+        }
+      }
+    }
+  }
+  Ok(None)
+}
+
+/// Parse a needsfile including everything it includes
+fn load_needsfile(path: &Path, content: &str, stack: &mut Vec<PathBuf>) -> Result<Vec<Entry>> {
+  let needsfile = crate::parser::parse(content)
+    .map_err(|e| parser::invalid_content(&e, &path.display().to_string(), content))?;
+  resolve_includes(path, content, needsfile, stack)
+}
+
+/// Append the entries of all included files, `stack` holds the files currently being read
+fn resolve_includes(
+  path: &Path,
+  content: &str,
+  needsfile: Needsfile,
+  stack: &mut Vec<PathBuf>,
+) -> Result<Vec<Entry>> {
+  let mut entries = needsfile.entries;
+  for entry in &mut entries {
+    entry.file = Some(path.to_path_buf());
+  }
+  if needsfile.includes.is_empty() {
+    return Ok(entries);
+  }
+
+  let current = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  stack.push(current);
+  let base_dir = path.parent().unwrap_or(Path::new("."));
+
+  for include in needsfile.includes {
+    let target = base_dir.join(&include.path);
+    let canonical = target.canonicalize().map_err(|source| IoError::FileRead {
+      path: target.clone(),
+      source,
+    })?;
+
+    if let Some(start) = stack.iter().position(|p| *p == canonical) {
+      let chain = stack[start..]
+        .iter()
+        .chain(std::iter::once(&canonical))
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ");
+      return Err(
+        ValidationError::IncludeCycle {
+          filename: path.display().to_string(),
+          chain,
+          source_code: NamedSource::new(path.display().to_string(), content.to_string()),
+          span: include.span.into(),
+        }
+        .into(),
+      );
+    }
+
+    let included = std::fs::read_to_string(&target).map_err(|source| IoError::FileRead {
+      path: target.clone(),
+      source,
+    })?;
+    debug!(from:display = path.display(), path:display = target.display(); "including needsfile");
+    let mut included = load_needsfile(&target, &included, stack)?;
+    // the entries belong to the section the include is under, like the ones written there
+    for entry in &mut included {
+      if entry.group.is_none() {
+        entry.group = include.group.clone();
+      }
+      if let Some(ref condition) = include.condition {
+        entry.conditions.insert(0, condition.clone());
+      }
+    }
+    entries.extend(included);
+  }
+
+  stack.pop();
+  Ok(entries)
+}
+
+/// Resolve a single include of the needsfile at `path` like a run would, for `needs lint`
+pub fn check_include(path: &Path, content: &str, include: Include) -> Result<()> {
+  let needsfile = Needsfile {
+    entries: Vec::new(),
+    includes: vec![include],
+  };
+  resolve_includes(path, content, needsfile, &mut Vec::new()).map(|_| ())
+}
+
+/// Read and parse a needsfile given with `--file`, `-` reads from stdin
+pub fn read_needsfile(path: &Path) -> Result<Vec<Entry>> {
+  let is_stdin = path == Path::new("-");
+  let path = if is_stdin { Path::new("<stdin>") } else { path };
+  let content = if is_stdin {
+    std::io::read_to_string(std::io::stdin()).map_err(|source| IoError::FileRead {
+      path: path.to_path_buf(),
+      source,
+    })?
+  } else {
+    std::fs::read_to_string(path).map_err(|source| IoError::FileRead {
+      path: path.to_path_buf(),
+      source,
+    })?
+  };
+
+  let entries = load_needsfile(path, &content, &mut Vec::new())?;
+  if entries.is_empty() {
+    return Err(
+      IoError::NeedsfileEmpty {
+        path: path.display().to_string(),
+      }
+      .into(),
+    );
+  }
+  debug!(path:display = path.display(), binaries:debug = &entries; "read needsfile");
+  Ok(entries)
+}
+
+/// `$XDG_CONFIG_HOME/needs` or `~/.config/needs`
+pub fn config_dir() -> Option<PathBuf> {
+  let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+    .filter(|dir| !dir.is_empty())
+    .map(PathBuf::from)
+    .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+  Some(config_dir.join("needs"))
+}
+
+/// The user-wide needsfile, `$XDG_CONFIG_HOME/needs/needsfile` or `~/.config/needs/needsfile`
+pub fn global_needsfile_path() -> Option<PathBuf> {
+  config_dir().map(|dir| dir.join("needsfile"))
+}
+
+fn read_global_needsfile() -> Result<Vec<Entry>> {
+  let Some(path) = global_needsfile_path().filter(|path| path.is_file()) else {
+    debug!("no global needsfile");
+    return Ok(Vec::new());
+  };
+  let content = std::fs::read_to_string(&path).map_err(|source| IoError::FileRead {
+    path: path.clone(),
+    source,
+  })?;
+  let entries = load_needsfile(&path, &content, &mut Vec::new())?;
+  info!(path:display = path.display(), count = entries.len(); "using global needsfile");
+  Ok(entries)
+}
+
+fn with_layer(entries: Vec<Entry>, layer: Layer) -> impl Iterator<Item = Entry> {
+  entries
+    .into_iter()
+    .map(move |entry| Entry { layer, ..entry })
+}
+
+/// Combine the entries for the same binary, the strictest settings win: requirements have to
+/// hold all together and an entry is only optional if it is everywhere. Entries only match in the
+/// same section with the same platform conditions, the first one keeps its layer and line.
+pub fn merge_entries(entries: Vec<Entry>) -> Vec<Entry> {
+  let mut merged: Vec<Entry> = Vec::new();
+  for entry in entries {
+    let Some(existing) = merged
+      .iter_mut()
+      .find(|e| e.name == entry.name && e.group == entry.group && e.conditions == entry.conditions)
+    else {
+      merged.push(entry);
+      continue;
+    };
+    info!(
+      bin = entry.name.as_str(),
+      kept:display = existing.layer,
+      merged:display = entry.layer;
+      "merged duplicate entry"
+    );
+    let entry_names = candidates(&entry);
+    existing.requirement = match (existing.requirement.take(), entry.requirement) {
+      (Some(mut req), Some(other)) => {
+        for comparator in other.comparators {
+          if !req.comparators.contains(&comparator) {
+            req.comparators.push(comparator);
+          }
+        }
+        Some(req)
+      }
+      (req, other) => req.or(other),
+    };
+    existing.optional &= entry.optional;
+    // `fd` and `fd | fdfind` accept any of the names
+    let mut names = candidates(existing);
+    for name in entry_names {
+      if !names.contains(&name) {
+        names.push(name);
+      }
+    }
+    existing.alternatives = if names.len() > 1 { names } else { Vec::new() };
+    existing.probe = ProbeOverride {
+      cmd: existing.probe.cmd.take().or(entry.probe.cmd),
+      stream: existing.probe.stream.or(entry.probe.stream),
+      regex: existing.probe.regex.take().or(entry.probe.regex),
+    };
+  }
+  merged
+}
+
+/// The names of an entry in order, just its name if it has no alternatives
+fn candidates(entry: &Entry) -> Vec<String> {
+  if entry.alternatives.is_empty() {
+    vec![entry.name.clone()]
+  } else {
+    entry.alternatives.clone()
+  }
+}
+
+/// Stdin can only be read once, so `-` may be given to `--file` at most one time
+fn check_files(files: &[PathBuf]) -> Result<()> {
+  if files.iter().filter(|path| *path == Path::new("-")).count() > 1 {
+    return Err(
+      ConfigError::Invalid {
+        reason: "stdin was given more than once to --file".to_string(),
+        advice: "Pass `-f -` once and combine the piped needsfiles into one stream.".to_string(),
+      }
+      .into(),
+    );
+  }
+  Ok(())
+}
 
 pub fn get_binary_names<'a>(cli: &Cli) -> Result<Vec<Binary<'a>>> {
+  check_files(&cli.files)?;
   let mut bail_cause =
     "No valid needsfile found.\nPlease provide a list of binaries or create a needsfile.";
-  let bins = match cli.bins.clone() {
-    Some(bins) => {
-      debug!(bins:debug = bins; "got bins from args");
-      bins
+  let mut bins = Vec::new();
+
+  match cli.bins.clone() {
+    Some(args) => {
+      debug!(bins:debug = args; "got bins from args");
+      for path in &cli.files {
+        bins.extend(with_layer(read_needsfile(path)?, Layer::File));
+      }
+      bins.extend(args.into_iter().map(Entry::new));
     }
     None => {
       debug!("no bins from args, trying to read from needsfiles");
-      let file_paths = ["needsfile", ".needsfile", "needs", ".needs"];
-      let mut bins = Vec::new();
-
-      for path in file_paths {
-        // Attempt to read from the first successful file path
-        match std::fs::read_to_string(path) {
-          Ok(content) => {
-            if content.trim().is_empty() {
-              warn!(path = path; "needsfile found but it is empty, trying next.");
-              continue; // Try next file if this one is empty
-            }
-            let names = match crate::parser::parse_needsfile(&content) {
-              Ok(names) => names,
-              Err(e) => {
-                warn!(path = path, error:display = e; "Failed to parse needsfile, trying next.");
-                continue;
-              }
-            };
-            if names.is_empty() {
-              warn!(path = path; "needsfile found but it is empty, trying next.");
-              continue; // Try next file if this one is empty
-            }
-
-            debug!(path:debug = path, binaries:debug = &names; "found needsfile");
-            bins.extend(names);
-            break;
-          }
-          Err(err) => {
-            debug!(path = path, error:display = err; "Failed to read or find needsfile, trying next.");
-          }
-        }
+      bins.extend(with_layer(read_global_needsfile()?, Layer::Global));
+
+      let cwd = std::env::current_dir().map_err(|source| IoError::FileRead {
+        path: PathBuf::from("."),
+        source,
+      })?;
+      if let Some((path, entries)) = find_needsfile(&cwd)? {
+        info!(path:display = path.display(); "using needsfile");
+        bins.extend(with_layer(entries, Layer::Project));
       }
+
+      for path in &cli.files {
+        let entries = read_needsfile(path)?;
+        info!(path:display = path.display(), count = entries.len(); "using needsfile from --file");
+        bins.extend(with_layer(entries, Layer::File));
+      }
+
       if bins.is_empty() {
         warn!("No valid needsfile found");
         return Err(IoError::NeedsfileMissing.into());
-      } else {
-        bins
       }
     }
-  };
+  }
+
+  let platform = Platform::current();
+  debug!(platform:debug = platform; "filtering entries for platform");
+  let bins = merge_entries(filter_platform(bins, &platform));
+  if bins.is_empty() {
+    info!("none of the needsfile entries apply to this platform");
+    return Ok(Vec::new());
+  }
+  for entry in &bins {
+    info!(bin = entry.name.as_str(), layer:display = entry.layer; "entry from layer");
+  }
 
   let binaries: Vec<Binary> = bins
-    .iter()
-    .filter(|name| !name.is_empty())
-    .map(|name| Binary::new(Cow::owned(name.clone())))
+    .into_iter()
+    .filter(|entry| !entry.name.is_empty())
+    .map(Binary::from_entry)
     .collect::<Vec<Binary>>();
 
   // LEAVE this here because sometimes collecting the binaries fails
@@ -76,3 +355,225 @@ pub fn get_binary_names<'a>(cli: &Cli) -> Result<Vec<Binary<'a>>> {
   }
   Ok(binaries)
 }
+
+/// Drop the entries whose platform conditions don't hold
+pub fn filter_platform(entries: Vec<Entry>, platform: &Platform) -> Vec<Entry> {
+  entries
+    .into_iter()
+    .filter(|entry| {
+      let applies = platform::applies(&entry.conditions, platform);
+      if !applies {
+        debug!(bin = entry.name.as_str(), conditions:debug = entry.conditions; "skipped, not for this platform");
+      }
+      applies
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_utils::TempTree;
+
+  fn temp_tree(name: &str) -> TempTree {
+    let root = TempTree::new(name);
+    std::fs::create_dir_all(root.join("repo/crates/sub")).unwrap();
+    root
+  }
+
+  #[test]
+  fn test_read_needsfile_missing() {
+    let result = read_needsfile(Path::new("/hopefully/non/existent/tools/ci.needs"));
+    let err_string = format!("{:?}", result.unwrap_err());
+    assert!(err_string.contains("needs::io::read_failed"));
+    assert!(err_string.contains("/hopefully/non/existent/tools/ci.needs"));
+  }
+
+  #[test]
+  fn test_read_needsfile() {
+    let root = temp_tree("file");
+    let path = root.join("ci.needs");
+    std::fs::write(&path, "cargo\ngit >=2\n").unwrap();
+    let entries = read_needsfile(&path).unwrap();
+    assert_eq!(entries.len(), 2);
+  }
+
+  #[test]
+  fn test_merge_entries() {
+    let global = crate::parser::parse_needsfile("rg >=13\nbat? fd").unwrap();
+    let project = crate::parser::parse_needsfile("rg >=14, <15\nbat\njust").unwrap();
+    let entries = with_layer(global, Layer::Global)
+      .chain(with_layer(project, Layer::Project))
+      .collect();
+
+    let merged = merge_entries(entries);
+    let names = merged.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["rg", "bat", "fd", "just"]);
+
+    let rg = &merged[0];
+    assert_eq!(rg.layer, Layer::Global);
+    let req = rg.requirement.as_ref().unwrap();
+    assert!(!req.matches(&semver::Version::new(13, 5, 0)));
+    assert!(req.matches(&semver::Version::new(14, 1, 0)));
+    assert!(!req.matches(&semver::Version::new(15, 0, 0)));
+
+    // required in the project, so it's not optional anymore
+    assert!(!merged[1].optional);
+    assert_eq!(merged[3].layer, Layer::Project);
+  }
+
+  #[test]
+  fn test_merge_entries_keeps_sections() {
+    let project = crate::parser::parse_needsfile("[build]\nls\n[lint]\nls\n").unwrap();
+    let merged = merge_entries(with_layer(project, Layer::Project).collect());
+    let groups = merged
+      .iter()
+      .map(|e| e.group.as_deref())
+      .collect::<Vec<_>>();
+    assert_eq!(groups, vec![Some("build"), Some("lint")]);
+  }
+
+  #[test]
+  fn test_merge_entries_same_file() {
+    let project = crate::parser::parse_needsfile("git\njust\ngit >=2").unwrap();
+    let merged = merge_entries(with_layer(project, Layer::Project).collect());
+    let names = merged.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["git", "just"]);
+    assert_eq!(merged[0].line, Some(1));
+    assert_eq!(
+      merged[0].requirement,
+      Some(semver::VersionReq::parse(">=2").unwrap())
+    );
+  }
+
+  #[test]
+  fn test_merge_entries_unions_alternatives() {
+    let global = crate::parser::parse_needsfile("fd\nbat | batcat").unwrap();
+    let project = crate::parser::parse_needsfile("fd | fdfind\nbat").unwrap();
+    let entries = with_layer(global, Layer::Global)
+      .chain(with_layer(project, Layer::Project))
+      .collect();
+
+    let merged = merge_entries(entries);
+    assert_eq!(merged.len(), 2);
+    assert_eq!(merged[0].alternatives, vec!["fd", "fdfind"]);
+    assert_eq!(merged[1].alternatives, vec!["bat", "batcat"]);
+  }
+
+  #[test]
+  fn test_read_needsfile_with_includes() {
+    let root = temp_tree("include");
+    std::fs::write(root.join("base.needs"), "# shared\ngit\n\ncargo\n").unwrap();
+    std::fs::write(
+      root.join("repo/ci.needs"),
+      "include ../base.needs # toolchain\njust\n",
+    )
+    .unwrap();
+
+    let entries = read_needsfile(&root.join("repo/ci.needs")).unwrap();
+    let names = entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["just", "git", "cargo"]);
+  }
+
+  #[test]
+  fn test_include_takes_section() {
+    let root = temp_tree("include_section");
+    std::fs::write(root.join("mac.needs"), "brew\n").unwrap();
+    std::fs::write(root.join("build.needs"), "cargo\n[lint]\nclippy\n").unwrap();
+    std::fs::write(
+      root.join("needsfile"),
+      "git\n[macos]\ninclude mac.needs\n[build]\ninclude build.needs\n",
+    )
+    .unwrap();
+
+    let entries = read_needsfile(&root.join("needsfile")).unwrap();
+    let groups = entries
+      .iter()
+      .map(|e| (e.name.as_str(), e.group.as_deref()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      groups,
+      vec![
+        ("git", None),
+        ("brew", None),
+        ("cargo", Some("build")),
+        ("clippy", Some("lint"))
+      ]
+    );
+
+    let linux = Platform {
+      os: "linux".to_string(),
+      arch: "x86_64".to_string(),
+      family: "unix".to_string(),
+      distro: Vec::new(),
+    };
+    let names = filter_platform(entries, &linux)
+      .into_iter()
+      .map(|e| e.name)
+      .collect::<Vec<_>>();
+    assert_eq!(names, vec!["git", "cargo", "clippy"]);
+  }
+
+  #[test]
+  fn test_read_needsfile_include_cycle() {
+    let root = temp_tree("cycle");
+    std::fs::write(root.join("a.needs"), "git\ninclude repo/b.needs\n").unwrap();
+    std::fs::write(root.join("repo/b.needs"), "cargo\ninclude ../a.needs\n").unwrap();
+
+    let result = read_needsfile(&root.join("a.needs"));
+    let err_string = format!("{:?}", result.unwrap_err());
+    assert!(err_string.contains("needs::validation::include_cycle"));
+  }
+
+  #[test]
+  fn test_find_needsfile_invalid_content() {
+    let root = temp_tree("invalid");
+    std::fs::write(root.join("repo/needsfile"), "git\nr$g\n").unwrap();
+    // used to be skipped in favor of this one
+    std::fs::write(root.join("repo/.needsfile"), "cargo\n").unwrap();
+
+    let result = find_needsfile(&root.join("repo"));
+    let err_string = format!("{:?}", result.unwrap_err());
+    assert!(err_string.contains("needs::validation::invalid_content"));
+  }
+
+  #[test]
+  fn test_find_needsfile_in_parent() {
+    let root = temp_tree("parent");
+    std::fs::create_dir(root.join("repo/.git")).unwrap();
+    std::fs::write(root.join("repo/needsfile"), "cargo git\n").unwrap();
+
+    let (path, entries) = find_needsfile(&root.join("repo/crates/sub"))
+      .unwrap()
+      .unwrap();
+    assert_eq!(path, root.join("repo/needsfile"));
+    assert_eq!(entries.len(), 2);
+
+    // a closer needsfile wins
+    std::fs::write(root.join("repo/crates/.needs"), "just\n").unwrap();
+    let (path, _) = find_needsfile(&root.join("repo/crates/sub"))
+      .unwrap()
+      .unwrap();
+    assert_eq!(path, root.join("repo/crates/.needs"));
+  }
+
+  #[test]
+  fn test_find_needsfile_stops_at_repo_root() {
+    let root = temp_tree("reporoot");
+    std::fs::create_dir(root.join("repo/.git")).unwrap();
+    // outside of the repository, must not be picked up
+    std::fs::write(root.join("needsfile"), "cargo\n").unwrap();
+
+    assert!(
+      find_needsfile(&root.join("repo/crates/sub"))
+        .unwrap()
+        .is_none()
+    );
+  }
+
+  #[test]
+  fn test_check_files_rejects_repeated_stdin() {
+    assert!(check_files(&[PathBuf::from("-"), PathBuf::from("ci.needs")]).is_ok());
+    assert!(check_files(&[PathBuf::from("-"), PathBuf::from("-")]).is_err());
+  }
+}
@@ -0,0 +1,233 @@
+use std::path::{Path, PathBuf};
+
+use log::{debug, info};
+use miette::{NamedSource, Report, Result};
+
+use crate::error::{IoError, ValidationError};
+use crate::parser;
+use crate::platform::{self, Condition};
+use crate::probes::Probes;
+use crate::syntax::{Document, Node};
+
+/// Resolve the needsfile for `lint` and `fmt`, the one `needs` would use if none is given
+pub fn target_path(path: Option<&Path>) -> Result<PathBuf> {
+  if let Some(path) = path {
+    return Ok(path.to_path_buf());
+  }
+  let cwd = std::env::current_dir().map_err(|source| IoError::FileRead {
+    path: PathBuf::from("."),
+    source,
+  })?;
+  crate::io::locate_needsfile(&cwd).ok_or_else(|| IoError::NeedsfileMissing.into())
+}
+
+pub fn read(path: &Path) -> Result<String> {
+  std::fs::read_to_string(path).map_err(|source| {
+    IoError::FileRead {
+      path: path.to_path_buf(),
+      source,
+    }
+    .into()
+  })
+}
+
+/// Check a needsfile for unparsable lines, broken includes, duplicates, builtins and platform
+/// conditions that never hold
+pub fn lint_content(path: &Path, content: &str, probes: &Probes) -> Vec<ValidationError> {
+  let filename = &path.display().to_string();
+  let document = Document::parse(content);
+  let source = || NamedSource::new(filename, content.to_string());
+  let mut problems = Vec::new();
+
+  // (name, group, conditions) of every entry so far, with its line
+  let mut seen: Vec<(String, Option<String>, Vec<Condition>, usize)> = Vec::new();
+  let mut group: Option<String> = None;
+  let mut section_condition: Option<Condition> = None;
+  let mut section_unreachable = false;
+
+  for line in &document.lines {
+    if let Some(ref err) = line.error {
+      problems.push(parser::invalid_content_at(
+        err,
+        filename,
+        content,
+        line.offset,
+      ));
+      continue;
+    }
+
+    for node in &line.nodes {
+      match node {
+        Node::Section {
+          group: section_group,
+          condition,
+          span,
+          ..
+        } => {
+          group = section_group.clone();
+          section_condition = condition.clone();
+          let reason = platform::unreachable_reason(condition.as_slice());
+          section_unreachable = reason.is_some();
+          if let Some(reason) = reason {
+            problems.push(ValidationError::UnreachablePlatform {
+              reason,
+              source_code: source(),
+              span: span.clone().into(),
+            });
+          }
+        }
+        Node::Include {
+          path: include_path,
+          span,
+        } => {
+          let include = parser::Include {
+            path: include_path.clone(),
+            span: span.clone(),
+            group: None,
+            condition: None,
+          };
+          if let Err(err) = crate::io::check_include(path, content, include) {
+            let mut reason = err
+              .chain()
+              .map(ToString::to_string)
+              .collect::<Vec<_>>()
+              .join(": ");
+            if let Some(help) = err.help() {
+              reason = format!("{}\n{}", reason, help);
+            }
+            problems.push(ValidationError::BrokenInclude {
+              path: include_path.clone(),
+              reason,
+              source_code: source(),
+              span: span.clone().into(),
+            });
+          }
+        }
+        Node::Entry { entry, span } => {
+          let mut conditions = section_condition.clone().into_iter().collect::<Vec<_>>();
+          conditions.extend(entry.conditions.iter().cloned());
+
+          if !section_unreachable && let Some(reason) = platform::unreachable_reason(&conditions) {
+            problems.push(ValidationError::UnreachablePlatform {
+              reason,
+              source_code: source(),
+              span: span.clone().into(),
+            });
+          }
+
+          if probes.is_builtin(&entry.name) {
+            problems.push(ValidationError::BuiltinEntry {
+              name: entry.name.clone(),
+              source_code: source(),
+              span: span.clone().into(),
+            });
+          }
+
+          let name = if entry.alternatives.is_empty() {
+            entry.name.clone()
+          } else {
+            entry.alternatives.join(" | ")
+          };
+          match seen
+            .iter()
+            .find(|(n, g, c, _)| *n == name && *g == group && *c == conditions)
+          {
+            Some((_, _, _, first_line)) => problems.push(ValidationError::DuplicateEntry {
+              name,
+              first_line: *first_line,
+              source_code: source(),
+              span: span.clone().into(),
+            }),
+            None => seen.push((name, group.clone(), conditions, line.number)),
+          }
+        }
+      }
+    }
+  }
+  problems
+}
+
+pub fn run(path: Option<&Path>, probes: &Probes) -> Result<()> {
+  let path = target_path(path)?;
+  let content = read(&path)?;
+  info!(path:display = path.display(); "linting needsfile");
+
+  let problems = lint_content(&path, &content, probes);
+  if problems.is_empty() {
+    debug!("no problems found");
+    return Ok(());
+  }
+
+  let count = problems.len();
+  for problem in problems {
+    eprintln!("{:?}", Report::new(problem));
+  }
+  eprintln!(
+    "{}: {} problem{} found",
+    path.display(),
+    count,
+    if count == 1 { "" } else { "s" }
+  );
+  std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn codes(content: &str) -> Vec<String> {
+    lint_content(Path::new("needsfile"), content, &Probes::embedded())
+      .into_iter()
+      .map(|problem| {
+        miette::Diagnostic::code(&problem)
+          .map(|code| code.to_string())
+          .unwrap_or_default()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_lint_clean() {
+    let content = "# tools\ngit cargo\nxclip @linux\n[macos]\npbcopy\n[build]\njust\n";
+    assert!(codes(content).is_empty());
+  }
+
+  #[test]
+  fn test_lint_problems() {
+    let content = "git\ncargo\ngit # again\nr$g\n[target_os = \"plan9\"]\nacme\n[linux]\npbcopy @macos\nxclip\n[build]\ngit\n";
+    let codes = codes(content);
+    assert_eq!(
+      codes,
+      vec![
+        "needs::validation::duplicate_entry",
+        "needs::validation::invalid_content",
+        "needs::validation::unreachable_platform",
+        "needs::validation::unreachable_platform",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_lint_builtin() {
+    assert_eq!(codes("cd\ngit\n"), vec!["needs::validation::builtin_entry"]);
+  }
+
+  #[test]
+  fn test_lint_includes() {
+    let root = crate::test_utils::TempTree::new("lint_include");
+    std::fs::write(root.join("base.needs"), "git\n").unwrap();
+    std::fs::write(root.join("a.needs"), "include b.needs\n").unwrap();
+    std::fs::write(root.join("b.needs"), "include a.needs\n").unwrap();
+    let content = "include base.needs\ninclude nope.needs\n[build]\ninclude a.needs\n";
+
+    let problems = lint_content(&root.join("needsfile"), content, &Probes::embedded());
+    let spans = problems
+      .iter()
+      .map(|problem| match problem {
+        ValidationError::BrokenInclude { span, .. } => &content[span.offset()..][..span.len()],
+        other => panic!("unexpected problem: {:?}", other),
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(spans, vec!["include nope.needs", "include a.needs"]);
+  }
+}
@@ -0,0 +1,296 @@
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+use log::{debug, info, warn};
+use miette::{NamedSource, Result};
+use semver::Version as SemVersion;
+use serde::{Deserialize, Serialize};
+
+use crate::binary::{self, Binary, sort_binaries};
+use crate::cache::VersionCache;
+use crate::cli::Cli;
+use crate::error::{ConfigError, IoError};
+use crate::versions::format_version;
+use crate::{discovery, io, probes, versions};
+
+pub const LOCKFILE_NAME: &str = "needs.lock";
+const LOCKFILE_VERSION: u32 = 1;
+const HEADER: &str =
+  "# Written by `needs lock`, compare this machine against it with `needs verify`.\n\n";
+
+/// A found binary as it was when the lockfile was written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Locked {
+  pub name: String,
+  pub path: PathBuf,
+  pub package_manager: Option<String>,
+  /// The full version, `None` if it couldn't be read
+  pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+  pub version: u32,
+  #[serde(default, rename = "binary")]
+  pub binaries: Vec<Locked>,
+}
+
+impl Lockfile {
+  pub fn from_binaries(binaries: &[Binary]) -> Self {
+    let binaries = binaries
+      .iter()
+      .map(|bin| Locked {
+        name: bin.name.to_string(),
+        path: bin.path.clone().unwrap_or_default(),
+        package_manager: bin.package_manager.clone(),
+        version: bin
+          .version
+          .as_ref()
+          .map(|version| format_version(version, true).to_string()),
+      })
+      .collect();
+    Self {
+      version: LOCKFILE_VERSION,
+      binaries,
+    }
+  }
+
+  pub fn parse(filename: &str, content: &str) -> Result<Self> {
+    let lockfile: Lockfile = toml::from_str(content).map_err(|err| ConfigError::Lockfile {
+      message: err.message().to_string(),
+      source_code: NamedSource::new(filename, content.to_string()),
+      span: err.span().unwrap_or(0..0).into(),
+    })?;
+    if lockfile.version != LOCKFILE_VERSION {
+      return Err(
+        ConfigError::Invalid {
+          reason: format!("{} has lockfile version {}", filename, lockfile.version),
+          advice: format!(
+            "This needs writes version {}, run `needs lock` again.",
+            LOCKFILE_VERSION
+          ),
+        }
+        .into(),
+      );
+    }
+    Ok(lockfile)
+  }
+
+  /// Fails for paths that aren't UTF-8
+  pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+    Ok(HEADER.to_string() + &toml::to_string(self)?)
+  }
+}
+
+/// A difference between the lockfile and this machine
+#[derive(Debug, Clone, PartialEq)]
+pub enum Drift {
+  Added(Locked),
+  Removed(Locked),
+  Upgraded { from: Locked, to: Locked },
+  Downgraded { from: Locked, to: Locked },
+}
+
+/// Compare by name, binaries without a readable version on either side only count when added or removed
+pub fn compare(locked: &[Locked], current: &[Locked]) -> Vec<Drift> {
+  let parse = |bin: &Locked| {
+    bin
+      .version
+      .as_deref()
+      .and_then(|v| SemVersion::parse(v).ok())
+  };
+  let mut drift = Vec::new();
+
+  for old in locked {
+    let Some(new) = current.iter().find(|bin| bin.name == old.name) else {
+      drift.push(Drift::Removed(old.clone()));
+      continue;
+    };
+    if let (Some(from), Some(to)) = (parse(old), parse(new)) {
+      let change = match to.cmp_precedence(&from) {
+        std::cmp::Ordering::Greater => Drift::Upgraded {
+          from: old.clone(),
+          to: new.clone(),
+        },
+        std::cmp::Ordering::Less => Drift::Downgraded {
+          from: old.clone(),
+          to: new.clone(),
+        },
+        std::cmp::Ordering::Equal => continue,
+      };
+      drift.push(change);
+    }
+  }
+  for new in current {
+    if !locked.iter().any(|bin| bin.name == new.name) {
+      drift.push(Drift::Added(new.clone()));
+    }
+  }
+  drift
+}
+
+/// Where `needs.lock` lives: next to the project needsfile, or in the current directory
+pub fn lockfile_path(path: Option<&Path>) -> Result<PathBuf> {
+  if let Some(path) = path {
+    return Ok(path.to_path_buf());
+  }
+  let cwd = std::env::current_dir().map_err(|source| IoError::FileRead {
+    path: PathBuf::from("."),
+    source,
+  })?;
+  let dir = io::locate_needsfile(&cwd)
+    .and_then(|needsfile| needsfile.parent().map(Path::to_path_buf))
+    .unwrap_or(cwd);
+  Ok(dir.join(LOCKFILE_NAME))
+}
+
+/// Find and probe every binary of the needsfiles, missing ones are left out
+fn current_binaries(cli: &Cli) -> Result<Vec<Locked>> {
+  let binaries = io::get_binary_names(cli)?;
+  let binaries = match cli.group {
+    Some(ref groups) => binary::filter_groups(binaries, groups)?,
+    None => binaries,
+  };
+  if binaries.is_empty() {
+    return Ok(Vec::new());
+  }
+  let (available, not_available) = discovery::partition_binaries(binaries)?;
+  debug!(not_available:debug = not_available; "not locked, not found:");
+
+  let probes = probes::load()?.with_timeout(cli.probe_timeout);
+  let cache = (!cli.no_cache).then(VersionCache::load);
+  let (mut found, mut outdated) =
+    versions::get_versions_for_bins(available, &probes, cache.as_ref());
+  if let Some(Err(err)) = cache.as_ref().map(VersionCache::save) {
+    warn!(error:display = err; "couldn't save the version cache");
+  }
+  found.append(&mut outdated);
+  sort_binaries(&mut found);
+  Ok(Lockfile::from_binaries(&found).binaries)
+}
+
+/// `needs lock`
+pub fn lock(cli: &Cli, path: Option<&Path>) -> Result<()> {
+  let path = lockfile_path(path)?;
+  let lockfile = Lockfile {
+    version: LOCKFILE_VERSION,
+    binaries: current_binaries(cli)?,
+  };
+  lockfile
+    .to_toml()
+    .map_err(std::io::Error::other)
+    .and_then(|content| std::fs::write(&path, content))
+    .map_err(|source| IoError::FileWrite {
+      path: path.clone(),
+      source,
+    })?;
+  info!(path:display = path.display(), count = lockfile.binaries.len(); "wrote lockfile");
+  Ok(())
+}
+
+/// `needs verify`, exits with 1 if anything changed
+pub fn verify(cli: &Cli, path: Option<&Path>) -> Result<()> {
+  let path = lockfile_path(path)?;
+  let content = std::fs::read_to_string(&path).map_err(|source| IoError::FileRead {
+    path: path.clone(),
+    source,
+  })?;
+  let lockfile = Lockfile::parse(&path.display().to_string(), &content)?;
+  let drift = compare(&lockfile.binaries, &current_binaries(cli)?);
+  if drift.is_empty() {
+    info!(path:display = path.display(); "matches the lockfile");
+    return Ok(());
+  }
+
+  let version = |bin: &Locked| bin.version.clone().unwrap_or_else(|| "?".to_string());
+  for change in &drift {
+    match change {
+      Drift::Added(bin) => println!("{:>10} {} {}", "added".green(), bin.name, version(bin)),
+      Drift::Removed(bin) => println!("{:>10} {} {}", "removed".red(), bin.name, version(bin)),
+      Drift::Upgraded { from, to } => println!(
+        "{:>10} {} {} -> {}",
+        "upgraded".yellow(),
+        to.name,
+        version(from),
+        version(to)
+      ),
+      Drift::Downgraded { from, to } => println!(
+        "{:>10} {} {} -> {}",
+        "downgraded".yellow(),
+        to.name,
+        version(from),
+        version(to)
+      ),
+    }
+  }
+  std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn locked(name: &str, version: Option<&str>) -> Locked {
+    Locked {
+      name: name.to_string(),
+      path: PathBuf::from("/usr/bin").join(name),
+      package_manager: None,
+      version: version.map(str::to_string),
+    }
+  }
+
+  #[test]
+  fn test_lockfile_roundtrip() {
+    let lockfile = Lockfile {
+      version: LOCKFILE_VERSION,
+      binaries: vec![
+        Locked {
+          package_manager: Some("cargo".to_string()),
+          ..locked("rg", Some("14.1.1"))
+        },
+        locked("ls", None),
+      ],
+    };
+    let content = lockfile.to_toml().unwrap();
+    assert!(content.contains("[[binary]]"));
+    assert!(content.contains("package-manager = \"cargo\""));
+    assert_eq!(Lockfile::parse(LOCKFILE_NAME, &content).unwrap(), lockfile);
+
+    assert!(Lockfile::parse(LOCKFILE_NAME, "version = 99\n").is_err());
+  }
+
+  #[test]
+  fn test_compare() {
+    let before = vec![
+      locked("git", Some("2.39.5")),
+      locked("node", Some("20.1.0")),
+      locked("fd", Some("10.2.0")),
+      locked("ls", None),
+      locked("jq", Some("1.7.1")),
+    ];
+    let after = vec![
+      locked("git", Some("2.43.0")),
+      locked("node", Some("18.0.0")),
+      locked("ls", None),
+      locked("jq", Some("1.7.1")),
+      locked("rg", Some("14.1.1")),
+    ];
+    let drift = compare(&before, &after);
+    assert_eq!(
+      drift,
+      vec![
+        Drift::Upgraded {
+          from: before[0].clone(),
+          to: after[0].clone()
+        },
+        Drift::Downgraded {
+          from: before[1].clone(),
+          to: after[1].clone()
+        },
+        Drift::Removed(before[2].clone()),
+        Drift::Added(after[4].clone()),
+      ]
+    );
+  }
+}
@@ -4,28 +4,42 @@
 #![allow(dead_code)]
 
 mod binary;
+#[cfg(feature = "version-retrieval")]
+mod cache;
 mod cli;
 mod discovery;
 mod error;
+mod fmt;
+mod formats;
 mod io;
+mod lint;
+#[cfg(feature = "version-retrieval")]
+mod lock;
 mod logging;
 mod output;
 mod parser;
+mod platform;
+mod probes;
+mod syntax;
+mod terminal;
+#[cfg(test)]
+mod test_utils;
 mod versions;
 
 use clap::Parser;
 use colored::Colorize;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use miette::{Report, Result};
 
 use crate::binary::{Binary, sort_binaries};
-use crate::error::{AppError, DiscoveryError};
+use crate::error::AppError;
 
 fn main() -> Result<()> {
   miette::set_panic_hook();
-  let cli = cli::Cli::parse();
+  let mut cli = cli::Cli::parse();
 
   logging::setup_logger(cli.verbosity)?;
+  terminal::setup_color(cli.color);
 
   debug!("Starting needs with verbosity level {}", cli.verbosity);
   debug!("passed bins: {:?}", cli.bins);
@@ -40,9 +54,30 @@ fn main() -> Result<()> {
   {
     debug!("Version retrieval NOT enabled.");
   }
-  // debug!("atty stdout: {}", is(Stream::Stdout));
-  // debug!("atty stderr: {}", is(Stream::Stderr));
-  // debug!("atty stdin: {}", is(Stream::Stdin));
+  cli.resolve_shorthands();
+
+  match cli.command {
+    Some(cli::Command::Lint { ref path }) => return lint::run(path.as_deref(), &probes::load()?),
+    Some(cli::Command::Fmt { ref path, check }) => return fmt::run(path.as_deref(), check),
+    #[cfg(feature = "version-retrieval")]
+    Some(cli::Command::Lock { ref path }) => return lock::lock(&cli, path.as_deref()),
+    #[cfg(feature = "version-retrieval")]
+    Some(cli::Command::Verify { ref path }) => return lock::verify(&cli, path.as_deref()),
+    #[cfg(feature = "version-retrieval")]
+    Some(cli::Command::Cache {
+      action: cli::CacheCommand::Clear,
+    }) => return cache::clear(),
+    Some(cli::Command::Which { .. }) | None => {}
+  }
+
+  debug!("stdout is a terminal: {}", terminal::stdout_is_tty());
+
+  // a broken template should fail before any binary is run
+  let template = cli
+    .template
+    .as_deref()
+    .map(formats::template::load)
+    .transpose()?;
 
   // TODO: split this up
   let binaries_from_source: Vec<Binary<'_>> = match io::get_binary_names(&cli) {
@@ -52,74 +87,138 @@ fn main() -> Result<()> {
       return Err(err);
     }
   };
-  if binaries_from_source.is_empty() {
-    error!("No binaries found, binary sources are empty");
-    return Err(DiscoveryError::NoBinariesSpecified.into());
-  }
+  let binaries_from_source = match cli.group {
+    Some(ref groups) => binary::filter_groups(binaries_from_source, groups)?,
+    None => binaries_from_source,
+  };
+  let groups = binary::group_names(&binaries_from_source);
 
   // Calculate max_name_len from all initial binaries for consistent padding
   let global_max_name_len: usize = binaries_from_source
     .iter()
-    .map(|bin| bin.name.len())
+    .flat_map(|bin| bin.candidates())
+    .map(|name| name.len())
     .max()
     .unwrap_or(0);
 
+  // a missing or empty needsfile already failed, nothing left means none of it is for this platform
   let (mut available, mut not_available): (Vec<Binary<'_>>, Vec<Binary<'_>>) =
-    discovery::partition_binaries(binaries_from_source)?;
+    if binaries_from_source.is_empty() {
+      (Vec::new(), Vec::new())
+    } else {
+      discovery::partition_binaries(binaries_from_source)?
+    };
+
+  if cli.all {
+    discovery::find_shadowed(&mut available);
+  }
+
+  let (mut not_available, mut missing_optional): (Vec<Binary<'_>>, Vec<Binary<'_>>) =
+    not_available.into_iter().partition(|bin| !bin.optional);
 
   let stay_quiet = cli.quiet;
 
+  if stay_quiet && !not_available.is_empty() {
+    info!(not_available:debug = not_available; "quiet exit, not found:");
+    std::process::exit(1);
+  }
+
+  #[cfg(feature = "version-retrieval")]
+  let (mut available, mut outdated) = {
+    // binaries with a version requirement always need their version, even with --no-versions
+    let retrieve_versions = !cli.no_versions && (!stay_quiet || cli.warn_shadowed);
+    let (to_probe, mut skipped): (Vec<Binary<'_>>, Vec<Binary<'_>>) = available
+      .into_iter()
+      .partition(|bin| retrieve_versions || bin.requirement.is_some());
+    let probes = probes::load()?.with_timeout(cli.probe_timeout);
+    let cache = (!cli.no_cache).then(cache::VersionCache::load);
+    let (mut up_to_date, outdated) =
+      versions::get_versions_for_bins(to_probe, &probes, cache.as_ref());
+    if let Some(Err(err)) = cache.as_ref().map(cache::VersionCache::save) {
+      warn!(error:display = err; "couldn't save the version cache");
+    }
+    up_to_date.append(&mut skipped);
+    (up_to_date, outdated)
+  };
+  #[cfg(not(feature = "version-retrieval"))]
+  let mut outdated: Vec<Binary<'_>> = Vec::new();
+
+  // optional binaries are shown as outdated but don't fail the check
+  let has_outdated = outdated.iter().any(|bin| !bin.optional);
+  let shadowing: Vec<&str> = available
+    .iter()
+    .chain(&outdated)
+    .filter(|bin| bin.shadows_newer())
+    .map(|bin| bin.name.as_ref())
+    .collect();
+  if !shadowing.is_empty() {
+    warn!(binaries:debug = shadowing; "a newer copy is hidden further down the PATH");
+  }
+  #[cfg(feature = "version-retrieval")]
+  let fail_on_shadowed = cli.warn_shadowed && !shadowing.is_empty();
+  #[cfg(not(feature = "version-retrieval"))]
+  let fail_on_shadowed = false;
+
   if stay_quiet {
-    if !not_available.is_empty() {
-      info!(not_available:debug = not_available; "quiet exit, not found:");
+    if has_outdated {
+      info!(outdated:debug = outdated; "quiet exit, outdated:");
+      std::process::exit(1);
+    }
+    if fail_on_shadowed {
+      info!("quiet exit, newer copies are shadowed");
       std::process::exit(1);
     }
     info!("quiet exit, all found");
     std::process::exit(0);
   }
 
-  sort_binaries(&mut available);
-  sort_binaries(&mut not_available);
+  let mut report = output::Report {
+    available,
+    outdated,
+    not_available,
+    missing_optional,
+  };
+  report.sort();
 
-  let needs_separator = !available.is_empty() && !not_available.is_empty();
+  #[cfg(feature = "version-retrieval")]
+  let full_versions = cli.full_versions;
+  #[cfg(not(feature = "version-retrieval"))]
+  let full_versions = false;
 
-  if !available.is_empty() {
-    #[cfg(feature = "version-retrieval")]
-    {
-      let retrieve_versions = !cli.no_versions;
-      let processed_available = if retrieve_versions {
-        let mut bins_with_versions = versions::get_versions_for_bins(available);
-        sort_binaries(&mut bins_with_versions); // Re-sort after potential version changes
-        bins_with_versions
-      } else {
-        available // Use as-is if not retrieving versions
-      };
-
-      output::print_center_aligned(
-        processed_available,
+  // on GitHub Actions the normal report also gets annotations and a step summary
+  if cli.format == formats::Format::Human
+    && template.is_none()
+    && formats::github::is_github_actions()
+  {
+    formats::github::print_report(&report)?;
+  }
+  if let Some(ref template) = template {
+    formats::template::print_report(&report, template)?;
+  } else {
+    match cli.format {
+      formats::Format::Human => output::print_report(
+        report,
+        &groups,
         global_max_name_len,
-        false,
-        cli.full_versions,
-      )?;
-    }
-    #[cfg(not(feature = "version-retrieval"))]
-    {
-      output::print_center_aligned(available, global_max_name_len)?;
+        full_versions,
+        cli.layout,
+      )?,
+      formats::Format::Json => formats::json::print_report(&report)?,
+      formats::Format::Junit => formats::junit::print_report(&report)?,
+      formats::Format::Tap => formats::tap::print_report(&report)?,
+      formats::Format::Github => formats::github::print_report(&report)?,
+      formats::Format::Markdown => formats::markdown::print_report(&report)?,
+      formats::Format::Csv => formats::csv::print_report(&report)?,
     }
   }
 
-  if needs_separator {
-    let padding = " ".repeat(global_max_name_len.saturating_sub(1));
-    println!("{}---", padding);
+  if has_outdated {
+    info!("exit, some binaries don't meet their version requirement");
+    std::process::exit(1);
   }
-
-  if !not_available.is_empty() {
-    for binary in not_available {
-      // Align "not found" with the names of "found" items.
-      let padding_needed = global_max_name_len.saturating_sub(binary.name.len());
-      let padding = " ".repeat(padding_needed);
-      println!("{}{} not found", padding, binary.name.red());
-    }
+  if fail_on_shadowed {
+    info!("exit, some binaries hide a newer copy further down the PATH");
+    std::process::exit(1);
   }
 
   Ok(())
@@ -1,9 +1,319 @@
-use crate::binary::Binary;
+use crate::binary::{Binary, sort_binaries};
 use crate::error::ValidationError;
+use crate::terminal;
 use crate::versions::format_version;
+use clap::ValueEnum;
 use colored::Colorize;
+use log::debug;
 use miette::Result;
 
+/// How the human readable report is laid out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+  /// one binary per line, the names aligned on the right
+  #[default]
+  Linear,
+  /// side-by-side columns sized to the terminal, linear with `--all` and plain lines when stdout isn't a terminal
+  Grid,
+}
+
+/// The result of a check, split into the sections that get printed
+#[derive(Debug, Default)]
+pub struct Report<'a> {
+  pub available: Vec<Binary<'a>>,
+  pub outdated: Vec<Binary<'a>>,
+  pub not_available: Vec<Binary<'a>>,
+  pub missing_optional: Vec<Binary<'a>>,
+}
+
+impl<'a> Report<'a> {
+  pub fn sort(&mut self) {
+    sort_binaries(&mut self.available);
+    sort_binaries(&mut self.outdated);
+    sort_binaries(&mut self.not_available);
+    sort_binaries(&mut self.missing_optional);
+  }
+
+  /// Take out the binaries of one needsfile section (`None` for the ungrouped ones)
+  pub fn take_group(&mut self, group: Option<&str>) -> Report<'a> {
+    fn take<'a>(binaries: &mut Vec<Binary<'a>>, group: Option<&str>) -> Vec<Binary<'a>> {
+      let (taken, rest) = std::mem::take(binaries)
+        .into_iter()
+        .partition(|bin| bin.group.as_deref() == group);
+      *binaries = rest;
+      taken
+    }
+
+    Report {
+      available: take(&mut self.available, group),
+      outdated: take(&mut self.outdated, group),
+      not_available: take(&mut self.not_available, group),
+      missing_optional: take(&mut self.missing_optional, group),
+    }
+  }
+
+  /// Whether `--all` looked up the other copies of the found binaries
+  pub fn has_copies(&self) -> bool {
+    self
+      .available
+      .iter()
+      .chain(&self.outdated)
+      .any(|bin| bin.shadowed.is_some())
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.available.is_empty()
+      && self.outdated.is_empty()
+      && self.not_available.is_empty()
+      && self.missing_optional.is_empty()
+  }
+}
+
+/// Print the report, with one `[header]` per needsfile section if there are any
+pub fn print_report(
+  mut report: Report,
+  groups: &[String],
+  max_len: usize,
+  full_versions: bool,
+  layout: Layout,
+) -> Result<()> {
+  // scripts get one unpadded line per binary
+  let plain = !terminal::stdout_is_tty();
+  let (layout, max_len) = if plain {
+    debug!("stdout is not a terminal, printing plain lines");
+    (Layout::Linear, 0)
+  } else if layout == Layout::Grid && report.has_copies() {
+    // the copies of `--all` are listed below their binary, which a grid has no room for
+    debug!("listing copies, using the linear layout");
+    (Layout::Linear, max_len)
+  } else {
+    (layout, max_len)
+  };
+  let print = |report: Report| match layout {
+    Layout::Linear => print_sections(report, max_len, full_versions, !plain),
+    Layout::Grid => print_grid(report, full_versions, terminal::width()),
+  };
+  if groups.is_empty() {
+    return print(report);
+  }
+
+  let ungrouped = report.take_group(None);
+  let mut first = ungrouped.is_empty();
+  if !first {
+    print(ungrouped)?;
+  }
+
+  for group in groups {
+    let section = report.take_group(Some(group));
+    if section.is_empty() {
+      continue;
+    }
+    if !first {
+      println!();
+    }
+    first = false;
+    let header = format!("[{}]", group);
+    let padding = match layout {
+      Layout::Linear => " ".repeat(max_len.saturating_sub(header.len())),
+      Layout::Grid => String::new(),
+    };
+    println!("{}{}", padding, header.bold());
+    print(section)?;
+  }
+  Ok(())
+}
+
+/// A grid cell, `width` is what it takes on screen without the color codes
+struct Cell {
+  width: usize,
+  text: String,
+}
+
+impl Cell {
+  fn new(name: String, status: String, styled_name: String, styled_status: String) -> Self {
+    let width = name.chars().count() + 1 + status.chars().count();
+    Self {
+      width,
+      text: format!("{} {}", styled_name, styled_status),
+    }
+  }
+}
+
+/// One cell per binary, found ones first, then outdated and missing ones
+fn grid_cells(report: Report, full_versions: bool) -> Vec<Cell> {
+  let version = |bin: &Binary| match bin.version {
+    Some(ref version) => format_version(version, full_versions).to_string(),
+    None if bin.timed_out => "timed out".to_string(),
+    None if cfg!(feature = "version-retrieval") => "?".to_string(),
+    None => "found".to_string(),
+  };
+  let mut cells = Vec::new();
+  for bin in &report.available {
+    let status = version(bin);
+    cells.push(Cell::new(
+      bin.display_name(),
+      status.clone(),
+      bin.display_name().green().to_string(),
+      status,
+    ));
+  }
+  for bin in &report.outdated {
+    let status = version(bin);
+    cells.push(Cell::new(
+      bin.display_name(),
+      status.clone(),
+      bin.display_name().yellow().to_string(),
+      status,
+    ));
+  }
+  for bin in &report.not_available {
+    cells.push(Cell::new(
+      bin.display_name(),
+      "not found".to_string(),
+      bin.display_name().red().to_string(),
+      "not found".to_string(),
+    ));
+  }
+  for bin in &report.missing_optional {
+    cells.push(Cell::new(
+      bin.display_name(),
+      "missing".to_string(),
+      bin.display_name().yellow().to_string(),
+      "missing".dimmed().to_string(),
+    ));
+  }
+  cells
+}
+
+/// Lay the cells out top to bottom, then left to right, in as many columns as fit in `width`
+fn grid_lines(cells: &[Cell], width: usize) -> Vec<String> {
+  const GAP: usize = 3;
+  let Some(cell_width) = cells.iter().map(|cell| cell.width).max() else {
+    return Vec::new();
+  };
+  let columns = ((width + GAP) / (cell_width + GAP)).max(1);
+  let rows = cells.len().div_ceil(columns);
+
+  (0..rows)
+    .map(|row| {
+      let mut line = String::new();
+      let mut cells_in_row = (row..cells.len()).step_by(rows).peekable();
+      while let Some(index) = cells_in_row.next() {
+        let cell = &cells[index];
+        line.push_str(&cell.text);
+        if cells_in_row.peek().is_some() {
+          line.push_str(&" ".repeat(cell_width - cell.width + GAP));
+        }
+      }
+      line
+    })
+    .collect()
+}
+
+fn print_grid(report: Report, full_versions: bool, width: usize) -> Result<()> {
+  for line in grid_lines(&grid_cells(report, full_versions), width) {
+    println!("{}", line);
+  }
+  Ok(())
+}
+
+/// Print found, outdated and missing binaries, separated by `---` if `separators` is set
+fn print_sections(
+  report: Report,
+  max_len: usize,
+  full_versions: bool,
+  separators: bool,
+) -> Result<()> {
+  let separator = " ".repeat(max_len.saturating_sub(1)) + "---";
+  let mut needs_separator = false;
+
+  if !report.available.is_empty() {
+    needs_separator = separators;
+    #[cfg(feature = "version-retrieval")]
+    print_center_aligned(report.available, max_len, false, full_versions)?;
+    #[cfg(not(feature = "version-retrieval"))]
+    print_center_aligned(report.available, max_len)?;
+  }
+
+  #[cfg(feature = "version-retrieval")]
+  if !report.outdated.is_empty() {
+    if needs_separator {
+      println!("{}", separator);
+    }
+    needs_separator = separators;
+    print_outdated(report.outdated, max_len, full_versions)?;
+  }
+
+  if !report.not_available.is_empty() {
+    if needs_separator {
+      println!("{}", separator);
+    }
+    needs_separator = separators;
+    print_not_found(report.not_available, max_len)?;
+  }
+
+  if !report.missing_optional.is_empty() {
+    if needs_separator {
+      println!("{}", separator);
+    }
+    print_missing_optional(report.missing_optional, max_len)?;
+  }
+  Ok(())
+}
+
+/// Shows which group a binary was picked from, e.g. ` (fd | fdfind)`
+fn alternatives_suffix(bin: &Binary) -> String {
+  if bin.alternatives.is_empty() {
+    String::new()
+  } else {
+    format!(" {}", format!("({})", bin.display_name()).dimmed())
+  }
+}
+
+/// `?` for a version that couldn't be read, `timed out` if the probe took too long
+#[cfg(feature = "version-retrieval")]
+fn unknown_version_text(bin: &Binary) -> String {
+  if bin.timed_out {
+    "timed out".yellow().to_string()
+  } else {
+    "?".to_string()
+  }
+}
+
+/// With `--all`, where the binary was found and the copies it hides, newer ones highlighted
+fn print_copies(bin: &Binary, max_len: usize, full_versions: bool) {
+  let (Some(shadowed), Some(path)) = (&bin.shadowed, &bin.path) else {
+    return;
+  };
+  let padding = " ".repeat(max_len + 1);
+  println!("{}{}", padding, path.display().to_string().dimmed());
+  for copy in shadowed {
+    let version = match copy.version {
+      Some(ref version) => format!("{} ", format_version(version, full_versions)),
+      None if cfg!(feature = "version-retrieval") => "? ".to_string(),
+      None => String::new(),
+    };
+    let via = match copy.package_manager {
+      Some(ref pm) => format!("{} ", format!("via {}", pm).dimmed()),
+      None => String::new(),
+    };
+    let newer = if bin.is_newer(copy) {
+      format!(" {}", "newer, shadowed".yellow().bold())
+    } else {
+      String::new()
+    };
+    println!(
+      "{}{} {}{}{}{}",
+      padding,
+      "└".dimmed(),
+      version,
+      via,
+      copy.path.display().to_string().dimmed(),
+      newer
+    );
+  }
+}
+
 #[cfg(feature = "version-retrieval")]
 pub fn print_center_aligned(
   binaries: Vec<Binary>,
@@ -32,14 +342,25 @@ pub fn print_center_aligned(
         }
         None => {
           if let Some(ref pm) = bin.package_manager {
-            format!("? {}", format!("via {}", pm).dimmed())
+            format!(
+              "{} {}",
+              unknown_version_text(bin),
+              format!("via {}", pm).dimmed()
+            )
           } else {
-            "?".to_string()
+            unknown_version_text(bin)
           }
         }
       }
     };
-    println!("{}{} {}", padding, bin.name.green(), version_display);
+    println!(
+      "{}{} {}{}",
+      padding,
+      bin.name.green(),
+      version_display,
+      alternatives_suffix(bin)
+    );
+    print_copies(bin, max_len, full_versions);
   }
   Ok(())
 }
@@ -54,7 +375,122 @@ pub fn print_center_aligned(binaries: Vec<Binary>, max_len: usize) -> Result<()>
     } else {
       "found".to_string()
     };
-    println!("{}{} {}", padding, bin.name.green(), display_text);
+    println!(
+      "{}{} {}{}",
+      padding,
+      bin.name.green(),
+      display_text,
+      alternatives_suffix(bin)
+    );
+    print_copies(bin, max_len, false);
   }
   Ok(())
 }
+
+#[cfg(feature = "version-retrieval")]
+pub fn print_outdated(binaries: Vec<Binary>, max_len: usize, full_versions: bool) -> Result<()> {
+  for bin in &binaries {
+    let padding_needed = max_len.saturating_sub(bin.name.len());
+    let padding = " ".repeat(padding_needed);
+    let version_str = match bin.version {
+      Some(ref version) => format!("{}", format_version(version, full_versions)),
+      None => unknown_version_text(bin),
+    };
+    let requirement = match bin.requirement {
+      Some(ref req) if bin.optional => format!("needs {} (optional)", req),
+      Some(ref req) => format!("needs {}", req),
+      None => "".to_string(),
+    };
+    println!(
+      "{}{} {} {}{}",
+      padding,
+      bin.name.yellow(),
+      version_str,
+      requirement.dimmed(),
+      alternatives_suffix(bin)
+    );
+    print_copies(bin, max_len, full_versions);
+  }
+  Ok(())
+}
+
+pub fn print_not_found(binaries: Vec<Binary>, max_len: usize) -> Result<()> {
+  for bin in &binaries {
+    // Align the first name with the names of "found" items, the other alternatives trail behind it.
+    let padding_needed = max_len.saturating_sub(bin.name.len());
+    let padding = " ".repeat(padding_needed);
+    let names = bin
+      .candidates()
+      .iter()
+      .map(|name| name.red().to_string())
+      .collect::<Vec<_>>()
+      .join(" | ");
+    println!("{}{} not found", padding, names);
+  }
+  Ok(())
+}
+
+pub fn print_missing_optional(binaries: Vec<Binary>, max_len: usize) -> Result<()> {
+  for bin in &binaries {
+    let padding_needed = max_len.saturating_sub(bin.name.len());
+    let padding = " ".repeat(padding_needed);
+    let names = bin
+      .candidates()
+      .iter()
+      .map(|name| name.yellow().to_string())
+      .collect::<Vec<_>>()
+      .join(" | ");
+    println!("{}{} missing {}", padding, names, "(optional)".dimmed());
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn cells(texts: &[&str]) -> Vec<Cell> {
+    texts
+      .iter()
+      .map(|text| Cell {
+        width: text.len(),
+        text: text.to_string(),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_grid_lines() {
+    let cells = cells(&[
+      "git 2.39.5",
+      "rg 14.1.1",
+      "fd ?",
+      "bat not found",
+      "jq 1.7.1",
+    ]);
+    // 13 wide cells with a gap of 3 fit twice into 30 columns
+    assert_eq!(
+      grid_lines(&cells, 30),
+      vec![
+        "git 2.39.5      bat not found",
+        "rg 14.1.1       jq 1.7.1",
+        "fd ?",
+      ]
+    );
+    assert_eq!(grid_lines(&cells, 100).len(), 1);
+    // a narrow terminal still gets one column
+    assert_eq!(grid_lines(&cells, 5).len(), 5);
+    assert!(grid_lines(&[], 80).is_empty());
+  }
+
+  #[test]
+  fn test_has_copies() {
+    let mut report = Report {
+      available: vec![Binary::new(beef::Cow::borrowed("git"))],
+      ..Report::default()
+    };
+    assert!(!report.has_copies());
+    report.available[0].shadowed = Some(Vec::new());
+    assert!(report.has_copies());
+  }
+}
@@ -1,58 +1,701 @@
 use pest::Parser;
+use pest::error::{Error, ErrorVariant};
 use pest_derive::Parser;
+use semver::VersionReq;
+use std::path::PathBuf;
+
+use crate::error::ValidationError;
+use crate::platform::Condition;
 
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
 pub struct NeedsParser;
 
-pub fn parse_needsfile(content: &str) -> Result<Vec<String>, pest::error::Error<Rule>> {
-    let pairs = NeedsParser::parse(Rule::needsfile, content)?;
-    let mut binaries = Vec::new();
-
-    for pair in pairs {
-        // The top-level rule is needsfile, which contains binary rules (since line is silent)
-        for inner_pair in pair.into_inner() {
-            match inner_pair.as_rule() {
-                Rule::binary => {
-                    binaries.push(inner_pair.as_str().to_string());
-                }
-                _ => {}
-            }
+/// Where an entry was configured, later layers add to the earlier ones
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Layer {
+  /// `$XDG_CONFIG_HOME/needs/needsfile`
+  Global,
+  /// The needsfile found in the current directory or its parents
+  Project,
+  /// A needsfile given with `--file`
+  File,
+  /// Binaries given as command line arguments
+  #[default]
+  Args,
+}
+
+impl std::fmt::Display for Layer {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Layer::Global => write!(f, "global"),
+      Layer::Project => write!(f, "project"),
+      Layer::File => write!(f, "file"),
+      Layer::Args => write!(f, "args"),
+    }
+  }
+}
+
+/// Which output of a version command holds the version
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+  Stdout,
+  Stderr,
+  /// Stdout first, then stderr
+  #[default]
+  Both,
+}
+
+impl Stream {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "stdout" => Some(Stream::Stdout),
+      "stderr" => Some(Stream::Stderr),
+      "both" => Some(Stream::Both),
+      _ => None,
+    }
+  }
+}
+
+impl std::fmt::Display for Stream {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Stream::Stdout => write!(f, "stdout"),
+      Stream::Stderr => write!(f, "stderr"),
+      Stream::Both => write!(f, "both"),
+    }
+  }
+}
+
+/// Per-entry overrides of how the version is probed, from `version-cmd=`, `stream=` and `version-regex=`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProbeOverride {
+  /// Arguments to run the binary with instead of trying the usual version flags
+  pub cmd: Option<String>,
+  pub stream: Option<Stream>,
+  /// Used instead of the default version pattern, the first capture group is the version
+  pub regex: Option<String>,
+}
+
+/// A single line item of a needsfile: a binary and an optional version requirement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+  pub name: String,
+  /// All names of an `a | b` group in order, empty for a single name
+  pub alternatives: Vec<String>,
+  pub requirement: Option<VersionReq>,
+  /// Marked with a trailing `?`, missing it is not an error
+  pub optional: bool,
+  /// The `[section]` the entry is listed under
+  pub group: Option<String>,
+  /// Platforms the entry is limited to, from `@name` and the section header
+  pub conditions: Vec<Condition>,
+  pub probe: ProbeOverride,
+  pub layer: Layer,
+  /// The needsfile the entry is from, `None` for binaries given as arguments
+  pub file: Option<PathBuf>,
+  /// The line of the entry in its needsfile, starting at 1
+  pub line: Option<usize>,
+}
+
+impl Entry {
+  pub fn new(name: String) -> Self {
+    Self {
+      name,
+      alternatives: Vec::new(),
+      requirement: None,
+      optional: false,
+      group: None,
+      conditions: Vec::new(),
+      probe: ProbeOverride::default(),
+      layer: Layer::default(),
+      file: None,
+      line: None,
+    }
+  }
+}
+
+impl std::fmt::Display for Entry {
+  /// The entry the way it's written on its line, without the conditions of its section
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.alternatives.is_empty() {
+      write!(f, "{}", self.name)?;
+    } else {
+      write!(f, "{}", self.alternatives.join(" | "))?;
+    }
+    if self.optional {
+      write!(f, "?")?;
+    }
+    if let Some(ref req) = self.requirement {
+      write!(f, " {}", req)?;
+    }
+    if let Some(ref cmd) = self.probe.cmd {
+      write!(f, " version-cmd=\"{}\"", cmd)?;
+    }
+    if let Some(stream) = self.probe.stream {
+      write!(f, " stream={}", stream)?;
+    }
+    if let Some(ref regex) = self.probe.regex {
+      write!(f, " version-regex=\"{}\"", regex)?;
+    }
+    for condition in &self.conditions {
+      write!(f, " @{}", condition.value())?;
+    }
+    Ok(())
+  }
+}
+
+/// An `include other.needs` statement, resolved by the caller since it needs the file's location
+#[derive(Debug, Clone, PartialEq)]
+pub struct Include {
+  pub path: String,
+  /// Byte range of the statement, to point diagnostics at it
+  pub span: std::ops::Range<usize>,
+  /// The `[section]` the statement is under, given to the included entries without one
+  pub group: Option<String>,
+  /// The platform condition of the section the statement is under
+  pub condition: Option<Condition>,
+}
+
+/// The contents of a single needsfile, without its includes resolved
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Needsfile {
+  pub entries: Vec<Entry>,
+  pub includes: Vec<Include>,
+}
+
+impl Needsfile {
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty() && self.includes.is_empty()
+  }
+}
+
+/// Parse only the entries of a needsfile, ignoring its includes
+pub fn parse_needsfile(content: &str) -> Result<Vec<Entry>, Error<Rule>> {
+  parse(content).map(|needsfile| needsfile.entries)
+}
+
+pub fn parse(content: &str) -> Result<Needsfile, Error<Rule>> {
+  let pairs = NeedsParser::parse(Rule::needsfile, content)?;
+  let mut entries = Vec::new();
+  let mut includes = Vec::new();
+  let mut group = None;
+  let mut section_condition = None;
+
+  for pair in pairs {
+    // The top-level rule is needsfile, which contains section and entry rules
+    for inner_pair in pair.into_inner() {
+      match inner_pair.as_rule() {
+        Rule::section => {
+          // every header starts a new section, either a group or a platform one
+          (group, section_condition) = parse_section(inner_pair)?;
+        }
+        Rule::entry => {
+          let mut entry = parse_entry(inner_pair)?;
+          entry.group = group.clone();
+          if let Some(ref condition) = section_condition {
+            entry.conditions.insert(0, condition.clone());
+          }
+          entries.push(entry);
         }
+        Rule::include => {
+          let span = inner_pair.as_span();
+          if let Some(path) = inner_pair.into_inner().next() {
+            includes.push(Include {
+              path: path.as_str().to_string(),
+              span: span.start()..span.end(),
+              group: group.clone(),
+              condition: section_condition.clone(),
+            });
+          }
+        }
+        _ => {}
+      }
     }
+  }
 
-    Ok(binaries)
+  Ok(Needsfile { entries, includes })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Turn a pest error into a diagnostic that points at the offending token
+pub fn invalid_content(err: &Error<Rule>, filename: &str, content: &str) -> ValidationError {
+  invalid_content_at(err, filename, content, 0)
+}
+
+/// Like `invalid_content`, for errors from parsing a single line that starts at `offset`
+pub fn invalid_content_at(
+  err: &Error<Rule>,
+  filename: &str,
+  content: &str,
+  offset: usize,
+) -> ValidationError {
+  let (start, end) = match err.location {
+    pest::error::InputLocation::Pos(pos) => (pos + offset, pos + offset),
+    pest::error::InputLocation::Span((start, end)) => (start + offset, end + offset),
+  };
 
-    #[test]
-    fn test_parse_simple() {
-        let content = "git\ncargo\nnode";
-        let binaries = parse_needsfile(content).unwrap();
-        assert_eq!(binaries, vec!["git", "cargo", "node"]);
+  // widen the location to the whitespace separated word around it
+  let is_separator = |c: char| c.is_whitespace();
+  let start = content[..start]
+    .char_indices()
+    .rev()
+    .find(|&(_, c)| is_separator(c))
+    .map(|(i, c)| i + c.len_utf8())
+    .unwrap_or(0);
+  let end = content[end..]
+    .find(is_separator)
+    .map(|i| i + end)
+    .unwrap_or(content.len())
+    .max(start);
+  let token = &content[start..end];
+
+  let advice = match err.variant {
+    ErrorVariant::CustomError { ref message } => message.clone(),
+    _ => {
+      let is_name_char = |c: char| c.is_ascii_alphanumeric() || "_-.".contains(c);
+      let suggestion = token
+        .chars()
+        .filter(|c| is_name_char(*c))
+        .collect::<String>();
+      if token.starts_with('[') && !token.contains(']') {
+        "section headers need a closing `]`, e.g. `[build]` or `[target_os = \"linux\"]`"
+          .to_string()
+      } else if !token.starts_with(['[', '@', '#']) && !suggestion.is_empty() && suggestion != token
+      {
+        format!(
+          "did you mean `{}`? Binary names may only contain letters, digits, `_`, `-` and `.`",
+          suggestion
+        )
+      } else {
+        err
+          .clone()
+          .renamed_rules(rule_name)
+          .variant
+          .message()
+          .to_string()
+      }
     }
+  };
+
+  ValidationError::InvalidContent {
+    filename: filename.to_string(),
+    token: token.to_string(),
+    source_code: miette::NamedSource::new(filename, content.to_string()),
+    span: (start, end - start).into(),
+    advice: Some(advice),
+  }
+}
 
-    #[test]
-    fn test_parse_with_comments() {
-        let content = "git\n# this is a comment\ncargo # inline comment\nnode";
-        let binaries = parse_needsfile(content).unwrap();
-        assert_eq!(binaries, vec!["git", "cargo", "node"]);
+/// Readable names for the grammar rules in error messages
+fn rule_name(rule: &Rule) -> String {
+  match rule {
+    Rule::EOI => "end of file",
+    Rule::binary | Rule::alternatives => "a binary name",
+    Rule::optional => "`?`",
+    Rule::requirement => "a version requirement like `>=1.2`",
+    Rule::platform => "a platform like `@linux`",
+    Rule::section | Rule::section_name => "a `[section]`",
+    Rule::condition_pair => "`key = \"value\"`",
+    Rule::include => "`include <path>`",
+    Rule::setting | Rule::setting_key => "a setting like `stream=stderr`",
+    other => return format!("{:?}", other),
+  }
+  .to_string()
+}
+
+pub fn parse_section(
+  pair: pest::iterators::Pair<'_, Rule>,
+) -> Result<(Option<String>, Option<Condition>), Error<Rule>> {
+  let Some(inner) = pair.into_inner().next() else {
+    return Ok((None, None));
+  };
+  match inner.as_rule() {
+    Rule::condition_pair => {
+      let span = inner.as_span();
+      let mut parts = inner.into_inner();
+      let key = parts.next().map(|p| p.as_str()).unwrap_or_default();
+      let value = parts
+        .next()
+        .and_then(|quoted| quoted.into_inner().next())
+        .map(|p| p.as_str())
+        .unwrap_or_default();
+      match Condition::from_key_value(key, value) {
+        Some(condition) => Ok((None, Some(condition))),
+        None => Err(Error::new_from_span(
+          ErrorVariant::CustomError {
+            message: format!(
+              "unknown platform key '{}', expected target_os, target_arch, target_family or distro",
+              key
+            ),
+          },
+          span,
+        )),
+      }
+    }
+    _ => {
+      let name = inner.as_str();
+      match Condition::from_known_name(name) {
+        Some(condition) => Ok((None, Some(condition))),
+        None => Ok((Some(name.to_string()), None)),
+      }
+    }
+  }
+}
+
+pub fn parse_entry(pair: pest::iterators::Pair<'_, Rule>) -> Result<Entry, Error<Rule>> {
+  let (line, _) = pair.line_col();
+  let mut names = Vec::new();
+  let mut requirement = None;
+  let mut optional = false;
+  let mut conditions = Vec::new();
+  let mut probe = ProbeOverride::default();
+
+  for part in pair.into_inner() {
+    match part.as_rule() {
+      Rule::alternatives => {
+        names.extend(
+          part
+            .into_inner()
+            .filter(|bin| bin.as_rule() == Rule::binary)
+            .map(|bin| bin.as_str().to_string()),
+        );
+      }
+      Rule::optional => optional = true,
+      Rule::platform => {
+        if let Some(name) = part.into_inner().next() {
+          conditions.push(Condition::from_name(name.as_str()));
+        }
+      }
+      Rule::requirement => {
+        let req = VersionReq::parse(part.as_str()).map_err(|e| {
+          Error::new_from_span(
+            ErrorVariant::CustomError {
+              message: format!("invalid version requirement: {}", e),
+            },
+            part.as_span(),
+          )
+        })?;
+        requirement = Some(req);
+      }
+      Rule::setting => parse_setting(part, &mut probe)?,
+      _ => {}
     }
+  }
 
-    #[test]
-    fn test_parse_empty_lines() {
-        let content = "\n\ngit\n\n\ncargo\n";
-        let binaries = parse_needsfile(content).unwrap();
-        assert_eq!(binaries, vec!["git", "cargo"]);
+  let name = names[0].clone();
+  if names.len() == 1 {
+    names.clear();
+  }
+
+  Ok(Entry {
+    alternatives: names,
+    requirement,
+    optional,
+    conditions,
+    probe,
+    line: Some(line),
+    ..Entry::new(name)
+  })
+}
+
+fn parse_setting(
+  pair: pest::iterators::Pair<'_, Rule>,
+  probe: &mut ProbeOverride,
+) -> Result<(), Error<Rule>> {
+  let mut parts = pair.into_inner();
+  let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+    return Ok(());
+  };
+  let value_span = value.as_span();
+  let value = match value.as_rule() {
+    Rule::quoted => value.into_inner().as_str(),
+    _ => value.as_str(),
+  };
+  let custom_error =
+    |message: String, span| Error::new_from_span(ErrorVariant::CustomError { message }, span);
+
+  match key.as_str() {
+    "version-cmd" => probe.cmd = Some(value.to_string()),
+    "stream" => {
+      let stream = Stream::from_name(value).ok_or_else(|| {
+        custom_error(
+          format!(
+            "unknown stream `{}`, expected `stdout`, `stderr` or `both`",
+            value
+          ),
+          value_span,
+        )
+      })?;
+      probe.stream = Some(stream);
+    }
+    "version-regex" => {
+      regex::Regex::new(value)
+        .map_err(|e| custom_error(format!("invalid version regex: {}", e), value_span))?;
+      probe.regex = Some(value.to_string());
+    }
+    other => {
+      return Err(custom_error(
+        format!(
+          "unknown setting `{}`, expected `version-cmd`, `version-regex` or `stream`",
+          other
+        ),
+        key.as_span(),
+      ));
     }
-    
-    #[test]
-    fn test_parse_complex_names() {
-        let content = "ripgrep\nfd-find\npython3.9\n_underscore";
-        let binaries = parse_needsfile(content).unwrap();
-        assert_eq!(binaries, vec!["ripgrep", "fd-find", "python3.9", "_underscore"]);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn names(entries: Vec<Entry>) -> Vec<String> {
+    entries.into_iter().map(|e| e.name).collect()
+  }
+
+  #[test]
+  fn test_parse_simple() {
+    let content = "git\ncargo\nnode";
+    let binaries = names(parse_needsfile(content).unwrap());
+    assert_eq!(binaries, vec!["git", "cargo", "node"]);
+  }
+
+  #[test]
+  fn test_parse_with_comments() {
+    let content = "git\n# this is a comment\ncargo # inline comment\nnode";
+    let binaries = names(parse_needsfile(content).unwrap());
+    assert_eq!(binaries, vec!["git", "cargo", "node"]);
+  }
+
+  #[test]
+  fn test_parse_empty_lines() {
+    let content = "\n\ngit\n\n\ncargo\n";
+    let binaries = names(parse_needsfile(content).unwrap());
+    assert_eq!(binaries, vec!["git", "cargo"]);
+  }
+
+  #[test]
+  fn test_parse_line_numbers() {
+    let content = "# tools\ngit\n\n[build]\n  cargo rustc # inline comment\n";
+    let lines: Vec<_> = parse_needsfile(content)
+      .unwrap()
+      .into_iter()
+      .map(|e| e.line)
+      .collect();
+    assert_eq!(lines, vec![Some(2), Some(5), Some(5)]);
+  }
+
+  #[test]
+  fn test_parse_complex_names() {
+    let content = "ripgrep\nfd-find\npython3.9\n_underscore";
+    let binaries = names(parse_needsfile(content).unwrap());
+    assert_eq!(
+      binaries,
+      vec!["ripgrep", "fd-find", "python3.9", "_underscore"]
+    );
+  }
+
+  #[test]
+  fn test_parse_requirements() {
+    let content = "rg >=14.0\nnode ^20 # lts\ncargo ~1.85 git\nfd >=8, <11";
+    let entries = parse_needsfile(content).unwrap();
+    assert_eq!(
+      names(entries.clone()),
+      vec!["rg", "node", "cargo", "git", "fd"]
+    );
+    assert_eq!(
+      entries[0].requirement,
+      Some(VersionReq::parse(">=14.0").unwrap())
+    );
+    assert_eq!(
+      entries[1].requirement,
+      Some(VersionReq::parse("^20").unwrap())
+    );
+    assert_eq!(
+      entries[2].requirement,
+      Some(VersionReq::parse("~1.85").unwrap())
+    );
+    assert_eq!(entries[3].requirement, None);
+    assert_eq!(
+      entries[4].requirement,
+      Some(VersionReq::parse(">=8, <11").unwrap())
+    );
+  }
+
+  #[test]
+  fn test_parse_requirement_with_spaces() {
+    let entries = parse_needsfile("rg >= 14\nfd >= 8, < 11 # spaced\ngit").unwrap();
+    assert_eq!(names(entries.clone()), vec!["rg", "fd", "git"]);
+    assert_eq!(
+      entries[0].requirement,
+      Some(VersionReq::parse(">=14").unwrap())
+    );
+    assert_eq!(
+      entries[1].requirement,
+      Some(VersionReq::parse(">=8, <11").unwrap())
+    );
+  }
+
+  #[test]
+  fn test_parse_alternatives() {
+    let content = "fd | fdfind\nbat|batcat >=0.20 git";
+    let entries = parse_needsfile(content).unwrap();
+    assert_eq!(names(entries.clone()), vec!["fd", "bat", "git"]);
+    assert_eq!(entries[0].alternatives, vec!["fd", "fdfind"]);
+    assert_eq!(entries[1].alternatives, vec!["bat", "batcat"]);
+    assert_eq!(
+      entries[1].requirement,
+      Some(VersionReq::parse(">=0.20").unwrap())
+    );
+    assert!(entries[2].alternatives.is_empty());
+  }
+
+  #[test]
+  fn test_parse_optional() {
+    let content = "cargo\nbacon?\nfd | fdfind? >=8\nrg ? # nice to have";
+    let entries = parse_needsfile(content).unwrap();
+    assert_eq!(names(entries.clone()), vec!["cargo", "bacon", "fd", "rg"]);
+    let optional = entries.iter().map(|e| e.optional).collect::<Vec<_>>();
+    assert_eq!(optional, vec![false, true, true, true]);
+    assert_eq!(
+      entries[2].requirement,
+      Some(VersionReq::parse(">=8").unwrap())
+    );
+  }
+
+  #[test]
+  fn test_parse_sections() {
+    let content = "git\n[build]\ncargo # compiler\n\n[lint]\nclippy-driver taplo\n";
+    let entries = parse_needsfile(content).unwrap();
+    assert_eq!(
+      names(entries.clone()),
+      vec!["git", "cargo", "clippy-driver", "taplo"]
+    );
+    let groups = entries
+      .iter()
+      .map(|e| e.group.as_deref())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      groups,
+      vec![None, Some("build"), Some("lint"), Some("lint")]
+    );
+  }
+
+  #[test]
+  fn test_parse_platform_conditions() {
+    let content =
+      "xclip @linux\n[target_arch = \"x86_64\"]\nnasm @linux\n[macos]\nbrew\n[build]\ncargo";
+    let entries = parse_needsfile(content).unwrap();
+    assert_eq!(entries[0].conditions, vec![Condition::Os("linux".into())]);
+    assert_eq!(
+      entries[1].conditions,
+      vec![
+        Condition::Arch("x86_64".into()),
+        Condition::Os("linux".into())
+      ]
+    );
+    assert_eq!(entries[2].conditions, vec![Condition::Os("macos".into())]);
+    assert_eq!(entries[2].group, None);
+    assert!(entries[3].conditions.is_empty());
+    assert_eq!(entries[3].group.as_deref(), Some("build"));
+  }
+
+  #[test]
+  fn test_parse_unknown_platform_key() {
+    assert!(parse_needsfile("[target_vendor = \"apple\"]\nbrew").is_err());
+  }
+
+  #[test]
+  fn test_parse_probe_settings() {
+    let content = "java version-cmd=\"-version\" stream=stderr @linux git\nmytool >=2 version-regex=\"build (\\d+)\"\n";
+    let entries = parse_needsfile(content).unwrap();
+    assert_eq!(names(entries.clone()), vec!["java", "git", "mytool"]);
+    assert_eq!(
+      entries[0].probe,
+      ProbeOverride {
+        cmd: Some("-version".into()),
+        stream: Some(Stream::Stderr),
+        regex: None,
+      }
+    );
+    assert_eq!(entries[0].conditions, vec![Condition::Os("linux".into())]);
+    assert_eq!(entries[1].probe, ProbeOverride::default());
+    assert_eq!(entries[2].probe.regex.as_deref(), Some("build (\\d+)"));
+    // written back the way it was read
+    assert_eq!(
+      entries[0].to_string(),
+      "java version-cmd=\"-version\" stream=stderr @linux"
+    );
+
+    assert!(parse_needsfile("java stream=all").is_err());
+    assert!(parse_needsfile("java version-regex=\"(\"").is_err());
+    assert!(parse_needsfile("java timeout=2").is_err());
+  }
+
+  #[test]
+  fn test_parse_includes() {
+    let content = "# base tools\ninclude ../base.needs # shared\n\ngit\nincludes-tool\n  include\tci/extra.needs\n";
+    let needsfile = parse(content).unwrap();
+    assert_eq!(names(needsfile.entries), vec!["git", "includes-tool"]);
+    let paths = needsfile
+      .includes
+      .iter()
+      .map(|i| i.path.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(paths, vec!["../base.needs", "ci/extra.needs"]);
+    assert_eq!(
+      &content[needsfile.includes[0].span.clone()],
+      "include ../base.needs"
+    );
+
+    let needsfile = parse("[build]\ninclude a.needs\n[macos]\ninclude b.needs\n").unwrap();
+    assert_eq!(needsfile.includes[0].group.as_deref(), Some("build"));
+    assert_eq!(needsfile.includes[0].condition, None);
+    assert_eq!(needsfile.includes[1].group, None);
+    assert_eq!(
+      needsfile.includes[1].condition,
+      Some(Condition::from_name("macos"))
+    );
+  }
+
+  fn diagnostic(content: &str) -> (String, Option<String>) {
+    let err = parse(content).unwrap_err();
+    match invalid_content(&err, "needsfile", content) {
+      ValidationError::InvalidContent { token, advice, .. } => (token, advice),
+      other => panic!("unexpected error: {:?}", other),
     }
+  }
+
+  #[test]
+  fn test_invalid_content_suggestion() {
+    let (token, advice) = diagnostic("git\nr$g # ripgrep\ncargo");
+    assert_eq!(token, "r$g");
+    assert!(advice.unwrap().contains("did you mean `rg`?"));
+  }
+
+  #[test]
+  fn test_invalid_content_custom_message() {
+    let (token, advice) = diagnostic("git\nrg >=abc\n");
+    assert_eq!(token, ">=abc");
+    assert!(advice.unwrap().contains("invalid version requirement"));
+
+    let (token, advice) = diagnostic("[build\ngit\n");
+    assert_eq!(token, "[build");
+    assert!(advice.unwrap().contains("closing `]`"));
+  }
+
+  #[test]
+  fn test_invalid_content_multibyte_whitespace() {
+    // a no-break space is whitespace but two bytes long
+    let (token, _) = diagnostic("[target_os = \"a\u{a0}b\"\ngit\n");
+    assert_eq!(token, "b\"");
+  }
+
+  #[test]
+  fn test_parse_invalid_requirement() {
+    let result = parse_needsfile("rg >=abc");
+    assert!(result.is_err());
+  }
 }
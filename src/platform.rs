@@ -0,0 +1,275 @@
+use std::fmt::Display;
+
+const KNOWN_OS: [&str; 11] = [
+  "linux",
+  "macos",
+  "windows",
+  "freebsd",
+  "netbsd",
+  "openbsd",
+  "dragonfly",
+  "solaris",
+  "illumos",
+  "android",
+  "ios",
+];
+
+const KNOWN_ARCH: [&str; 11] = [
+  "x86",
+  "x86_64",
+  "arm",
+  "aarch64",
+  "riscv64",
+  "powerpc",
+  "powerpc64",
+  "s390x",
+  "loongarch64",
+  "mips",
+  "mips64",
+];
+
+const KNOWN_FAMILY: [&str; 2] = ["unix", "windows"];
+
+/// The system needs is running on, conditions of needsfile entries are checked against it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Platform {
+  pub os: String,
+  pub arch: String,
+  pub family: String,
+  /// `ID` and `ID_LIKE` from `/etc/os-release`, e.g. `["ubuntu", "debian"]`
+  pub distro: Vec<String>,
+}
+
+impl Platform {
+  pub fn current() -> Self {
+    let distro = std::fs::read_to_string("/etc/os-release")
+      .map(|content| parse_os_release(&content))
+      .unwrap_or_default();
+    Self {
+      os: std::env::consts::OS.to_string(),
+      arch: std::env::consts::ARCH.to_string(),
+      family: std::env::consts::FAMILY.to_string(),
+      distro,
+    }
+  }
+}
+
+/// Collect the distro ids from the content of an os-release file
+pub fn parse_os_release(content: &str) -> Vec<String> {
+  let mut ids = Vec::new();
+  for line in content.lines() {
+    let Some((key, value)) = line.split_once('=') else {
+      continue;
+    };
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+    match key.trim() {
+      "ID" => ids.insert(0, value.to_lowercase()),
+      "ID_LIKE" => ids.extend(value.split_whitespace().map(str::to_lowercase)),
+      _ => {}
+    }
+  }
+  ids
+}
+
+/// Limits a needsfile entry or section to some platforms, e.g. `@linux` or `[target_arch = "x86_64"]`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+  Os(String),
+  Arch(String),
+  Family(String),
+  Distro(String),
+}
+
+impl Condition {
+  /// Interpret a bare name like `linux` or `x86_64`, anything unknown is taken as a distro
+  pub fn from_name(name: &str) -> Self {
+    let name = name.to_lowercase();
+    if KNOWN_OS.contains(&name.as_str()) {
+      Condition::Os(name)
+    } else if KNOWN_ARCH.contains(&name.as_str()) {
+      Condition::Arch(name)
+    } else if KNOWN_FAMILY.contains(&name.as_str()) {
+      Condition::Family(name)
+    } else {
+      Condition::Distro(name)
+    }
+  }
+
+  /// Like `from_name` but only for os, arch and family names, used for `[section]` headers
+  pub fn from_known_name(name: &str) -> Option<Self> {
+    match Self::from_name(name) {
+      Condition::Distro(_) => None,
+      condition => Some(condition),
+    }
+  }
+
+  /// Interpret a `key = "value"` pair, `None` for unknown keys
+  pub fn from_key_value(key: &str, value: &str) -> Option<Self> {
+    let value = value.to_lowercase();
+    match key {
+      "target_os" | "os" => Some(Condition::Os(value)),
+      "target_arch" | "arch" => Some(Condition::Arch(value)),
+      "target_family" | "family" => Some(Condition::Family(value)),
+      "distro" => Some(Condition::Distro(value)),
+      _ => None,
+    }
+  }
+
+  /// The os, arch, family or distro name, as used in `@name`
+  pub fn value(&self) -> &str {
+    match self {
+      Condition::Os(value)
+      | Condition::Arch(value)
+      | Condition::Family(value)
+      | Condition::Distro(value) => value,
+    }
+  }
+
+  pub fn matches(&self, platform: &Platform) -> bool {
+    match self {
+      Condition::Os(os) => *os == platform.os,
+      Condition::Arch(arch) => *arch == platform.arch,
+      Condition::Family(family) => *family == platform.family,
+      Condition::Distro(distro) => platform.distro.contains(distro),
+    }
+  }
+}
+
+impl Display for Condition {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Condition::Os(os) => write!(f, "target_os = \"{}\"", os),
+      Condition::Arch(arch) => write!(f, "target_arch = \"{}\"", arch),
+      Condition::Family(family) => write!(f, "target_family = \"{}\"", family),
+      Condition::Distro(distro) => write!(f, "distro = \"{}\"", distro),
+    }
+  }
+}
+
+/// Why a set of conditions can't hold on any platform, `None` if it can
+pub fn unreachable_reason(conditions: &[Condition]) -> Option<String> {
+  let family_of_os = |os: &str| if os == "windows" { "windows" } else { "unix" };
+  let mut os: Option<&str> = None;
+  let mut arch: Option<&str> = None;
+  let mut family: Option<&str> = None;
+
+  for condition in conditions {
+    let (known, seen, key): (&[&str], &mut Option<&str>, &str) = match condition {
+      Condition::Os(_) => (&KNOWN_OS, &mut os, "target_os"),
+      Condition::Arch(_) => (&KNOWN_ARCH, &mut arch, "target_arch"),
+      Condition::Family(_) => (&KNOWN_FAMILY, &mut family, "target_family"),
+      Condition::Distro(_) => continue,
+    };
+    let value = condition.value();
+    if !known.contains(&value) {
+      return Some(format!(
+        "'{}' is not a known {}, expected one of: {}",
+        value,
+        key,
+        known.join(", ")
+      ));
+    }
+    match seen {
+      Some(previous) if *previous != value => {
+        return Some(format!(
+          "{} can't be both '{}' and '{}'",
+          key, previous, value
+        ));
+      }
+      _ => *seen = Some(value),
+    }
+  }
+
+  if let (Some(os), Some(family)) = (os, family)
+    && family_of_os(os) != family
+  {
+    return Some(format!(
+      "target_os '{}' is never part of target_family '{}'",
+      os, family
+    ));
+  }
+  None
+}
+
+/// Whether all conditions hold on the platform, no conditions always apply
+pub fn applies(conditions: &[Condition], platform: &Platform) -> bool {
+  conditions
+    .iter()
+    .all(|condition| condition.matches(platform))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parser::parse_needsfile;
+
+  fn fake_platform(os: &str, arch: &str, distro: &[&str]) -> Platform {
+    Platform {
+      os: os.to_string(),
+      arch: arch.to_string(),
+      family: if os == "windows" { "windows" } else { "unix" }.to_string(),
+      distro: distro.iter().map(|d| d.to_string()).collect(),
+    }
+  }
+
+  #[test]
+  fn test_parse_os_release() {
+    let content = "NAME=\"Ubuntu\"\nID=ubuntu\nID_LIKE=debian\nVERSION_ID=\"24.04\"\n";
+    assert_eq!(parse_os_release(content), vec!["ubuntu", "debian"]);
+    let content = "ID=\"fedora\"\n";
+    assert_eq!(parse_os_release(content), vec!["fedora"]);
+  }
+
+  #[test]
+  fn test_condition_from_name() {
+    assert_eq!(Condition::from_name("linux"), Condition::Os("linux".into()));
+    assert_eq!(
+      Condition::from_name("x86_64"),
+      Condition::Arch("x86_64".into())
+    );
+    assert_eq!(
+      Condition::from_name("unix"),
+      Condition::Family("unix".into())
+    );
+    assert_eq!(
+      Condition::from_name("debian"),
+      Condition::Distro("debian".into())
+    );
+    assert_eq!(Condition::from_known_name("build"), None);
+  }
+
+  #[test]
+  fn test_unreachable_reason() {
+    let linux = Condition::Os("linux".into());
+    assert_eq!(unreachable_reason(std::slice::from_ref(&linux)), None);
+    assert_eq!(
+      unreachable_reason(&[linux.clone(), Condition::Distro("arch".into())]),
+      None
+    );
+    assert!(unreachable_reason(&[linux.clone(), Condition::Os("macos".into())]).is_some());
+    assert!(unreachable_reason(&[linux, Condition::Family("windows".into())]).is_some());
+    assert!(unreachable_reason(&[Condition::Os("plan9".into())]).is_some());
+  }
+
+  #[test]
+  fn test_applies_with_fake_platform() {
+    let content = "git\nxclip @linux\npbcopy @macos\napt @debian\n[target_arch = \"aarch64\"]\nrosetta-tool\n[macos]\nbrew\n";
+    let entries = parse_needsfile(content).unwrap();
+
+    let ubuntu = fake_platform("linux", "x86_64", &["ubuntu", "debian"]);
+    let applicable = entries
+      .iter()
+      .filter(|e| applies(&e.conditions, &ubuntu))
+      .map(|e| e.name.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(applicable, vec!["git", "xclip", "apt"]);
+
+    let mac = fake_platform("macos", "aarch64", &[]);
+    let applicable = entries
+      .iter()
+      .filter(|e| applies(&e.conditions, &mac))
+      .map(|e| e.name.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(applicable, vec!["git", "pbcopy", "rosetta-tool", "brew"]);
+  }
+}
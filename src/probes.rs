@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::{debug, info};
+use miette::{NamedSource, Result};
+use serde::Deserialize;
+
+use crate::error::{ConfigError, IoError};
+use crate::parser::{ProbeOverride, Stream};
+
+/// The probe table that ships with needs
+const EMBEDDED_PROBES: &str = include_str!("probes.toml");
+
+/// How to get the version of a binary: what to run it with and where to look
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Probe {
+  #[serde(default = "default_args")]
+  pub args: Vec<String>,
+  #[serde(default)]
+  pub stream: Stream,
+  /// Used instead of the default version pattern, the first capture group is the version
+  pub regex: Option<String>,
+  /// Shell builtins and core utilities have no version and are never run
+  #[serde(default)]
+  pub builtin: bool,
+  /// How long the binary may take, from `--probe-timeout`
+  #[serde(skip, default = "default_timeout")]
+  pub timeout: Duration,
+}
+
+fn default_args() -> Vec<String> {
+  vec!["--version".to_string()]
+}
+
+pub fn default_timeout() -> Duration {
+  Duration::from_secs(5)
+}
+
+impl Default for Probe {
+  fn default() -> Self {
+    Self {
+      args: default_args(),
+      stream: Stream::default(),
+      regex: None,
+      builtin: false,
+      timeout: default_timeout(),
+    }
+  }
+}
+
+impl Probe {
+  /// Apply the `version-cmd=`, `stream=` and `version-regex=` of a needsfile entry
+  pub fn with_override(self, probe: &ProbeOverride) -> Self {
+    let mut result = self;
+    if let Some(ref cmd) = probe.cmd {
+      result.args = cmd.split_whitespace().map(str::to_string).collect();
+      result.builtin = false;
+    }
+    if let Some(stream) = probe.stream {
+      result.stream = stream;
+    }
+    if let Some(ref regex) = probe.regex {
+      result.regex = Some(regex.clone());
+    }
+    result
+  }
+}
+
+/// Probes by binary name, the embedded table extended by the user's `probes.toml`
+#[derive(Debug, Clone)]
+pub struct Probes {
+  probes: HashMap<String, Probe>,
+  timeout: Duration,
+}
+
+impl Default for Probes {
+  fn default() -> Self {
+    Self {
+      probes: HashMap::new(),
+      timeout: default_timeout(),
+    }
+  }
+}
+
+impl Probes {
+  pub fn embedded() -> Self {
+    // the table is checked by a test, it can't fail at runtime
+    Self::parse("probes.toml", EMBEDDED_PROBES).expect("embedded probe table is valid")
+  }
+
+  /// Parse a probe table, errors point at the offending spot of the file
+  pub fn parse(filename: &str, content: &str) -> Result<Self> {
+    let probes = toml::from_str::<HashMap<String, Probe>>(content).map_err(|err| {
+      let span = err.span().unwrap_or(0..0);
+      ConfigError::ProbeTable {
+        message: err.message().to_string(),
+        source_code: NamedSource::new(filename, content.to_string()),
+        span: span.into(),
+      }
+    })?;
+    for (name, probe) in &probes {
+      if let Some(ref pattern) = probe.regex {
+        regex::Regex::new(pattern).map_err(|err| ConfigError::Invalid {
+          reason: format!("invalid regex for '{}' in {}", name, filename),
+          advice: err.to_string(),
+        })?;
+      }
+    }
+    Ok(Self {
+      probes,
+      ..Self::default()
+    })
+  }
+
+  /// Entries of `other` replace the ones with the same name
+  pub fn extend(&mut self, other: Probes) {
+    self.probes.extend(other.probes);
+  }
+
+  pub fn with_timeout(self, timeout: Duration) -> Self {
+    Self { timeout, ..self }
+  }
+
+  pub fn get(&self, name: &str) -> Probe {
+    Probe {
+      timeout: self.timeout,
+      ..self.probes.get(name).cloned().unwrap_or_default()
+    }
+  }
+
+  pub fn is_builtin(&self, name: &str) -> bool {
+    self.probes.get(name).is_some_and(|probe| probe.builtin)
+  }
+}
+
+/// The user's probe table, `$XDG_CONFIG_HOME/needs/probes.toml` or `~/.config/needs/probes.toml`
+pub fn user_probes_path() -> Option<PathBuf> {
+  crate::io::config_dir().map(|dir| dir.join("probes.toml"))
+}
+
+/// The embedded probe table with the user's additions
+pub fn load() -> Result<Probes> {
+  let mut probes = Probes::embedded();
+  let Some(path) = user_probes_path().filter(|path| path.is_file()) else {
+    debug!("no user probe table");
+    return Ok(probes);
+  };
+  let content = std::fs::read_to_string(&path).map_err(|source| IoError::FileRead {
+    path: path.clone(),
+    source,
+  })?;
+  let user = Probes::parse(&path.display().to_string(), &content)?;
+  info!(path:display = path.display(), count = user.probes.len(); "using user probe table");
+  probes.extend(user);
+  Ok(probes)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_embedded_probes() {
+    let probes = Probes::embedded();
+    assert!(probes.is_builtin("cd"));
+    assert!(!probes.is_builtin("ssh"));
+    assert_eq!(probes.get("ssh").stream, Stream::Stderr);
+    assert_eq!(probes.get("go").args, vec!["version"]);
+    assert_eq!(probes.get("some-unknown-tool"), Probe::default());
+  }
+
+  #[test]
+  fn test_user_probes_replace_embedded() {
+    let mut probes = Probes::embedded();
+    let user = Probes::parse(
+      "probes.toml",
+      "[java]\nargs = [\"--version\"]\n\n[mytool]\nargs = [\"info\"]\nregex = 'build (\\d+)'\n",
+    )
+    .unwrap();
+    probes.extend(user);
+    assert_eq!(probes.get("java").stream, Stream::Both);
+    assert_eq!(probes.get("mytool").regex.as_deref(), Some(r"build (\d+)"));
+
+    assert!(Probes::parse("probes.toml", "[java]\nflag = \"-v\"\n").is_err());
+    assert!(Probes::parse("probes.toml", "[java]\nregex = '('\n").is_err());
+  }
+
+  #[test]
+  fn test_probe_with_override() {
+    let probe = Probe {
+      builtin: true,
+      ..Probe::default()
+    }
+    .with_override(&ProbeOverride {
+      cmd: Some("version --short".into()),
+      stream: Some(Stream::Stderr),
+      regex: None,
+    });
+    assert_eq!(probe.args, vec!["version", "--short"]);
+    assert_eq!(probe.stream, Stream::Stderr);
+    assert!(!probe.builtin);
+  }
+}
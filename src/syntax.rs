@@ -0,0 +1,186 @@
+use std::ops::Range;
+
+use pest::Parser;
+use pest::error::Error;
+
+use crate::parser::{self, Entry, NeedsParser, Rule};
+use crate::platform::Condition;
+
+/// A statement on a needsfile line, spans are byte ranges into the whole file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+  Section {
+    /// The header as written, e.g. `[build]` or `[target_os = "linux"]`
+    text: String,
+    group: Option<String>,
+    condition: Option<Condition>,
+    span: Range<usize>,
+  },
+  Include {
+    path: String,
+    span: Range<usize>,
+  },
+  Entry {
+    entry: Entry,
+    span: Range<usize>,
+  },
+}
+
+/// One line of a needsfile, unlike `parser::parse` this keeps the comments
+#[derive(Debug, Clone)]
+pub struct Line {
+  /// 1-based line number
+  pub number: usize,
+  /// Byte offset of the line in the file
+  pub offset: usize,
+  pub nodes: Vec<Node>,
+  /// The comment at the end of the line, including the `#`
+  pub comment: Option<String>,
+  /// Set if the line could not be parsed, `nodes` is empty then
+  pub error: Option<Error<Rule>>,
+}
+
+impl Line {
+  pub fn is_blank(&self) -> bool {
+    self.nodes.is_empty() && self.comment.is_none() && self.error.is_none()
+  }
+}
+
+/// A needsfile as a list of lines, parsed one by one so that every broken line is found
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+  pub lines: Vec<Line>,
+}
+
+impl Document {
+  pub fn parse(content: &str) -> Self {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for (index, raw) in content.split('\n').enumerate() {
+      let text = raw.strip_suffix('\r').unwrap_or(raw);
+      lines.push(parse_line(text, index + 1, offset));
+      offset += raw.len() + 1;
+    }
+    // a trailing newline doesn't start another line
+    if content.ends_with('\n') {
+      lines.pop();
+    }
+    Self { lines }
+  }
+
+  pub fn errors(&self) -> impl Iterator<Item = &Line> {
+    self.lines.iter().filter(|line| line.error.is_some())
+  }
+}
+
+fn parse_line(text: &str, number: usize, offset: usize) -> Line {
+  let mut line = Line {
+    number,
+    offset,
+    nodes: Vec::new(),
+    comment: None,
+    error: None,
+  };
+
+  let pairs = match NeedsParser::parse(Rule::needsfile, text) {
+    Ok(pairs) => pairs,
+    Err(err) => {
+      line.error = Some(err);
+      return line;
+    }
+  };
+
+  let shift = |span: pest::Span<'_>| span.start() + offset..span.end() + offset;
+  let mut nodes = Vec::new();
+  for pair in pairs.flatten() {
+    match pair.as_rule() {
+      // comments can also end up nested inside of an entry
+      Rule::COMMENT => line.comment = Some(pair.as_str().trim_end().to_string()),
+      Rule::section => {
+        let span = pair.as_span();
+        match parser::parse_section(pair.clone()) {
+          Ok((group, condition)) => nodes.push(Node::Section {
+            text: span.as_str().to_string(),
+            group,
+            condition,
+            span: shift(span),
+          }),
+          Err(err) => {
+            line.error = Some(err);
+            return line;
+          }
+        }
+      }
+      Rule::include => {
+        let span = pair.as_span();
+        if let Some(path) = pair.into_inner().next() {
+          nodes.push(Node::Include {
+            path: path.as_str().to_string(),
+            span: shift(span),
+          });
+        }
+      }
+      Rule::entry => {
+        let span = pair.as_span();
+        // the entry span can swallow a trailing comment, only point at the names
+        let names = pair
+          .clone()
+          .into_inner()
+          .flatten()
+          .filter(|p| p.as_rule() == Rule::binary)
+          .map(|p| p.as_span())
+          .collect::<Vec<_>>();
+        let names_span = match (names.first(), names.last()) {
+          (Some(first), Some(last)) => first.start()..last.end(),
+          _ => span.start()..span.end(),
+        };
+        match parser::parse_entry(pair) {
+          Ok(entry) => nodes.push(Node::Entry {
+            entry,
+            span: names_span.start + offset..names_span.end + offset,
+          }),
+          Err(err) => {
+            line.error = Some(err);
+            return line;
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+  line.nodes = nodes;
+  line
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_document_keeps_comments() {
+    let content = "# tools\ngit cargo # vcs and build\n\n[lint]\nr$g\ninclude base.needs\n";
+    let document = Document::parse(content);
+    assert_eq!(document.lines.len(), 6);
+
+    assert_eq!(document.lines[0].comment.as_deref(), Some("# tools"));
+    assert!(document.lines[0].nodes.is_empty());
+
+    let line = &document.lines[1];
+    assert_eq!(line.comment.as_deref(), Some("# vcs and build"));
+    assert_eq!(line.nodes.len(), 2);
+    if let Node::Entry { ref span, .. } = line.nodes[1] {
+      assert_eq!(&content[span.clone()], "cargo");
+    } else {
+      panic!("expected an entry");
+    }
+
+    assert!(document.lines[2].is_blank());
+    assert!(matches!(
+      document.lines[3].nodes[0],
+      Node::Section { ref group, .. } if group.as_deref() == Some("lint")
+    ));
+    assert_eq!(document.errors().count(), 1);
+    assert_eq!(document.errors().next().unwrap().number, 5);
+    assert!(matches!(document.lines[5].nodes[0], Node::Include { .. }));
+  }
+}
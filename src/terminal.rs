@@ -0,0 +1,70 @@
+//! What stdout is connected to and whether to color it
+
+use clap::ValueEnum;
+use log::debug;
+
+/// Used when the width of the terminal can't be read
+const DEFAULT_WIDTH: usize = 80;
+
+pub fn stdout_is_tty() -> bool {
+  atty::is(atty::Stream::Stdout)
+}
+
+/// The width of the terminal on stdout, `$COLUMNS` or 80 if it can't be asked
+pub fn width() -> usize {
+  #[cfg(unix)]
+  {
+    // SAFETY: TIOCGWINSZ only writes into the winsize struct it is given
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+      && size.ws_col > 0
+    {
+      return usize::from(size.ws_col);
+    }
+  }
+  std::env::var("COLUMNS")
+    .ok()
+    .and_then(|columns| columns.trim().parse().ok())
+    .filter(|&columns| columns > 0)
+    .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// `--color`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+  /// color when stdout is a terminal, unless `NO_COLOR` is set or `CLICOLOR_FORCE` asks for it
+  #[default]
+  Auto,
+  Always,
+  Never,
+}
+
+fn env_is_set(name: &str) -> bool {
+  std::env::var_os(name).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// `NO_COLOR` wins over `CLICOLOR_FORCE`, see no-color.org and bixense.com/clicolors
+pub fn use_color(choice: ColorChoice) -> bool {
+  match choice {
+    ColorChoice::Always => true,
+    ColorChoice::Never => false,
+    ColorChoice::Auto if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) => {
+      false
+    }
+    ColorChoice::Auto if env_is_set("CLICOLOR_FORCE") => true,
+    ColorChoice::Auto => stdout_is_tty(),
+  }
+}
+
+/// Turn colors on or off for the report, the log and error messages
+pub fn setup_color(choice: ColorChoice) {
+  let color = use_color(choice);
+  debug!(choice:debug = choice, color = color; "color");
+  colored::control::set_override(color);
+  if choice != ColorChoice::Auto {
+    // miette looks at stderr by itself, only a flag overrides that
+    let _ = miette::set_hook(Box::new(move |_| {
+      Box::new(miette::MietteHandlerOpts::new().color(color).build())
+    }));
+  }
+}
@@ -0,0 +1,30 @@
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A directory in the system temp dir for one test, removed again when dropped so that failing
+/// tests don't leave it behind
+pub struct TempTree(PathBuf);
+
+impl TempTree {
+  /// `needs_test_{name}_{pid}`, emptied first in case a killed run left it behind
+  pub fn new(name: &str) -> Self {
+    let root = std::env::temp_dir().join(format!("needs_test_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    Self(root)
+  }
+}
+
+impl Deref for TempTree {
+  type Target = Path;
+
+  fn deref(&self) -> &Path {
+    &self.0
+  }
+}
+
+impl Drop for TempTree {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.0);
+  }
+}
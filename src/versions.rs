@@ -1,5 +1,9 @@
-use crate::binary::Binary;
+use crate::binary::{Binary, Probed};
+#[cfg(feature = "version-retrieval")]
+use crate::cache::VersionCache;
 use crate::error::VersionError;
+use crate::parser::Stream;
+use crate::probes::{Probe, Probes};
 use beef::Cow;
 use log::{debug, error, info, trace, warn};
 use miette::Result;
@@ -8,7 +12,7 @@ use rayon::prelude::*;
 use regex::Regex;
 use semver::{BuildMetadata, Prerelease, Version as SemVersion};
 use std::fmt;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 pub fn format_version(value: &SemVersion, full_versions: bool) -> impl fmt::Display + '_ {
   struct Wrapper<'a>(&'a SemVersion, bool);
@@ -51,38 +55,6 @@ pub fn unknown_version() -> SemVersion {
     build: BuildMetadata::new("unknown").unwrap(),
   }
 }
-// list known binaries that dont have a version
-#[cfg(feature = "version-retrieval")]
-pub fn known_binaries() -> Vec<Cow<'static, str>> {
-  vec![
-    // shell builtins
-    Cow::borrowed("ls"),
-    Cow::borrowed("cd"),
-    Cow::borrowed("pwd"),
-    Cow::borrowed("echo"),
-    Cow::borrowed("cat"),
-    Cow::borrowed("find"),
-    Cow::borrowed("awk"), // no semver, just the date
-    Cow::borrowed("sed"),
-    Cow::borrowed("cut"),
-    //Cow::borrowed("sort"), // 2.3-Apple (195)
-    Cow::borrowed("uniq"),
-    Cow::borrowed("wc"),
-    Cow::borrowed("head"),
-    Cow::borrowed("tail"),
-    Cow::borrowed("chmod"),
-    Cow::borrowed("chown"),
-    Cow::borrowed("ln"),
-    Cow::borrowed("mkdir"),
-    Cow::borrowed("rmdir"),
-    Cow::borrowed("rm"),
-    Cow::borrowed("cp"),
-    Cow::borrowed("mv"),
-    Cow::borrowed("touch"),
-    Cow::borrowed("ssh"),
-    Cow::borrowed("nice"),
-  ]
-}
 
 #[cfg(feature = "version-retrieval")]
 pub static _SEMVER_REGEX: Lazy<Regex> = Lazy::new(|| {
@@ -129,8 +101,8 @@ pub fn clean_version_string(version_str: &str) -> String {
     }
   }
 
-  // Ensure we have at least 2 segments (major.minor)
-  while cleaned_version.len() == 2 {
+  // Pad to major.minor.patch, a custom version regex can match just a number
+  while cleaned_version.len() < 3 {
     cleaned_version.push("0".to_string());
   }
 
@@ -172,50 +144,111 @@ pub fn clean_version_string(version_str: &str) -> String {
   result
 }
 
-#[cfg(feature = "version-retrieval")]
-pub fn execute_binary<'a>(binary_name: &str) -> Result<Cow<'a, str>> {
-  // TODO: log the frequency of these
+/// What a version probe printed
+#[derive(Debug, Clone, Default)]
+pub struct ProbeOutput {
+  pub stdout: String,
+  pub stderr: String,
+}
 
-  use xshell::{Shell, cmd};
+impl ProbeOutput {
+  /// The streams to search for the version, in order
+  pub fn candidates(&self, stream: Stream) -> Vec<(Stream, &str)> {
+    match stream {
+      Stream::Stdout => vec![(Stream::Stdout, &self.stdout)],
+      Stream::Stderr => vec![(Stream::Stderr, &self.stderr)],
+      Stream::Both => vec![
+        (Stream::Stdout, &self.stdout),
+        (Stream::Stderr, &self.stderr),
+      ],
+    }
+  }
+}
 
-  let version_flags = ["--version", "-v", "-version", "-V"];
+/// Run a probe with stdin closed, its process group is killed once `probe.timeout` has passed
+#[cfg(feature = "version-retrieval")]
+pub fn execute_binary(binary_name: &str, probe: &Probe) -> Result<ProbeOutput> {
+  // TODO: log the frequency of these
 
-  for flag in &version_flags {
-    let sh = match Shell::new() {
-      Ok(s) => s,
-      Err(e) => {
-        error!(error:display = e; "Error creating shell");
-        return Err(
-          VersionError::Execution {
-            name: binary_name.to_string(),
-            source: std::io::Error::other(e),
-          }
-          .into(),
-        );
-      }
-    };
-    let name = binary_name;
-    let command = cmd!(sh, "{name}").ignore_stderr().arg(flag);
-    trace!(command:display = command; "Running command");
-
-    match command.read() {
-      Ok(output) => return Ok(Cow::owned(output)),
-      Err(err) => {
-        trace!(SCOPE = binary_name, err:display = err; "flag didn't work, error for tracing:");
-        debug!(SCOPE = binary_name, flag = flag; "flag didn't work, trying next...");
-        // Continue trying other flags - we only report error if all flags fail
-        continue;
-      }
-    };
-  }
-  info!(scope = binary_name; "no version flag found, see --help or check builtins");
-  Err(
+  use std::process::{Command, Stdio};
+  use std::sync::mpsc;
+
+  let args = &probe.args;
+  let mut command = Command::new(binary_name);
+  command
+    .args(args)
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped());
+  // own process group, so that whatever the binary starts can be killed along with it
+  #[cfg(unix)]
+  std::os::unix::process::CommandExt::process_group(&mut command, 0);
+  trace!(SCOPE = binary_name, args:debug = args, stream:display = probe.stream; "Running command");
+
+  let execution_error = |source: std::io::Error| {
+    trace!(SCOPE = binary_name, err:display = source; "probe didn't work, error for tracing:");
+    info!(scope = binary_name, args:debug = args; "version probe failed, add it to probes.toml or set version-cmd");
     VersionError::Execution {
       name: binary_name.to_string(),
-      source: std::io::Error::new(std::io::ErrorKind::NotFound, "No valid version flag found"),
+      source,
     }
-    .into(),
-  )
+  };
+
+  let child = command.spawn().map_err(execution_error)?;
+  let pid = child.id();
+  let (sender, receiver) = mpsc::channel();
+  std::thread::spawn(move || sender.send(child.wait_with_output()));
+
+  let output = match receiver.recv_timeout(probe.timeout) {
+    Ok(output) => output.map_err(execution_error)?,
+    Err(_) => {
+      warn!(SCOPE = binary_name, timeout:debug = probe.timeout; "probe timed out, killing it");
+      kill_process_group(pid);
+      return Err(
+        VersionError::Timeout {
+          name: binary_name.to_string(),
+          timeout: probe.timeout,
+        }
+        .into(),
+      );
+    }
+  };
+  if !output.status.success() {
+    return Err(
+      execution_error(std::io::Error::other(format!(
+        "exited with {}",
+        output.status
+      )))
+      .into(),
+    );
+  }
+
+  Ok(ProbeOutput {
+    stdout: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+  })
+}
+
+#[cfg(all(feature = "version-retrieval", unix))]
+fn kill_process_group(pid: u32) {
+  // SAFETY: kill has no memory safety requirements, a negative pid addresses the process group
+  let result = unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+  if result != 0 {
+    debug!(pid = pid, error:display = std::io::Error::last_os_error(); "failed to kill process group");
+  }
+}
+
+#[cfg(all(feature = "version-retrieval", not(unix)))]
+fn kill_process_group(pid: u32) {
+  // /T takes the child processes along
+  let result = std::process::Command::new("taskkill")
+    .args(["/F", "/T", "/PID", &pid.to_string()])
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null())
+    .status();
+  if let Err(err) = result {
+    debug!(pid = pid, error:display = err; "failed to kill process tree");
+  }
 }
 
 #[cfg(feature = "version-retrieval")]
@@ -223,34 +256,35 @@ pub fn extract_version<'a>(
   output: Cow<'a, str>,
   binary_name: Cow<'a, str>,
 ) -> Result<Cow<'a, str>> {
-  let lines = output
-    .lines()
-    .filter(|l| l.chars().any(|c| c.is_ascii_digit()))
-    .collect::<Vec<_>>();
+  extract_version_with(output, binary_name, &VER_REGEX)
+}
 
-  trace!(SCOPE = binary_name.as_ref(), lines:debug = lines; "filtered lines:");
+/// Like `extract_version` with a custom pattern, its first capture group (or the whole match) is the version
+#[cfg(feature = "version-retrieval")]
+pub fn extract_version_with<'a>(
+  output: Cow<'a, str>,
+  binary_name: Cow<'a, str>,
+  regex: &Regex,
+) -> Result<Cow<'a, str>> {
+  trace!(SCOPE = binary_name.as_ref(), output = output.as_ref(); "searching for a version");
 
-  for line in &lines {
-    // TODO: check all lines for more info (deno e.g.)
-    if let Some(captures) = VER_REGEX.captures(line) {
-      let version_string = &captures[1];
-      info!(SCOPE = binary_name.as_ref(), version:debug = version_string, line = line; "version found");
-
-      let version_string = clean_version_string(version_string);
-      debug!(SCOPE = binary_name.as_ref(), version:debug = version_string; "cleaned version");
-
-      // #[cfg(not(test))]
-      // {
-      //   let mut file = std::fs::OpenOptions::new()
-      //     .append(true)
-      //     .create(true)
-      //     .open("version_output.txt")
-      //     .unwrap();
-      //   writeln!(file, "(\"{}\", \"{}\"),", line, version_string).unwrap();
-      // }
-
-      return Ok(Cow::owned(version_string.to_string()));
-    }
+  if let Some(version_string) = match_version(&output, regex) {
+    info!(SCOPE = binary_name.as_ref(), version:debug = version_string; "version found");
+
+    let version_string = clean_version_string(version_string);
+    debug!(SCOPE = binary_name.as_ref(), version:debug = version_string; "cleaned version");
+
+    // #[cfg(not(test))]
+    // {
+    //   let mut file = std::fs::OpenOptions::new()
+    //     .append(true)
+    //     .create(true)
+    //     .open("version_output.txt")
+    //     .unwrap();
+    //   writeln!(file, "(\"{}\", \"{}\"),", line, version_string).unwrap();
+    // }
+
+    return Ok(Cow::owned(version_string.to_string()));
   }
 
   warn!(SCOPE = binary_name.as_ref(), output = output.as_ref(); "No valid version found in the output");
@@ -263,85 +297,237 @@ pub fn extract_version<'a>(
   )
 }
 
+/// The first match of `regex` on a line with a digit, its first capture group if it has one
+#[cfg(feature = "version-retrieval")]
+fn match_version<'o>(output: &'o str, regex: &Regex) -> Option<&'o str> {
+  output
+    .lines()
+    .filter(|l| l.chars().any(|c| c.is_ascii_digit()))
+    // TODO: check all lines for more info (deno e.g.)
+    .find_map(|line| {
+      let captures = regex.captures(line)?;
+      captures
+        .get(1)
+        .or_else(|| captures.get(0))
+        .map(|matched| matched.as_str())
+    })
+}
+
+/// A version read from the output of a probe
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoundVersion {
+  pub version: SemVersion,
+  /// As the binary printed it, before it was cleaned up for semver
+  pub raw: String,
+  pub stream: Stream,
+}
+
+/// The version of a binary and where it was found, `None` for builtins
 #[cfg(feature = "version-retrieval")]
-pub fn get_version(binary_name: Cow<str>) -> Result<Option<SemVersion>> {
+pub fn get_version(binary_name: Cow<str>, probe: &Probe) -> Result<Option<FoundVersion>> {
+  if probe.builtin {
+    return Ok(None);
+  }
+
+  let regex = match probe.regex {
+    Some(ref pattern) => {
+      Some(
+        Regex::new(pattern).map_err(|source| VersionError::InvalidRegex {
+          name: binary_name.to_string(),
+          pattern: pattern.clone(),
+          source,
+        })?,
+      )
+    }
+    None => None,
+  };
+
   let now = Instant::now();
-  let output = execute_binary(binary_name.as_ref());
+  let output = execute_binary(binary_name.as_ref(), probe);
   trace!(
       SCOPE = binary_name.as_ref(),
       ms = now.elapsed().as_millis();
       "calling binary took"
   );
-  match output {
-    Ok(output) => {
-      trace!(
-        SCOPE = binary_name.as_ref(), output = output.as_ref(); "command output");
-      let version_string = extract_version(output.clone(), binary_name.clone())?;
-
-      match SemVersion::parse(version_string.as_ref()) {
-        Ok(v) => {
-          debug!(SCOPE = binary_name.as_ref(), version:debug = v; "version parsed");
-          Ok(Some(v))
-        }
-        Err(e) => {
-          warn!(SCOPE = binary_name.as_ref(), error:display = e; "error parsing version");
-          Err(
-            VersionError::SemverParse {
-              version_string: version_string.to_string(),
-              source: e,
-            }
-            .into(),
-          )
+  let output = output?;
+  trace!(
+    SCOPE = binary_name.as_ref(), stdout = output.stdout.as_str(), stderr = output.stderr.as_str(); "command output");
+
+  let regex = regex.as_ref().unwrap_or(&VER_REGEX);
+  let mut found = None;
+  for (stream, text) in output.candidates(probe.stream) {
+    match match_version(text, regex) {
+      Some(raw) => {
+        found = Some((raw.to_string(), stream));
+        break;
+      }
+      None => {
+        debug!(SCOPE = binary_name.as_ref(), stream:display = stream; "no version on this stream")
+      }
+    }
+  }
+  let Some((raw, stream)) = found else {
+    warn!(SCOPE = binary_name.as_ref(); "No valid version found in the output");
+    return Err(
+      VersionError::VersionParse {
+        name: binary_name.to_string(),
+        output: format!("{}\n{}", output.stdout, output.stderr)
+          .trim()
+          .to_string(),
+      }
+      .into(),
+    );
+  };
+  let version_string = clean_version_string(&raw);
+  debug!(SCOPE = binary_name.as_ref(), raw = raw.as_str(), version = version_string.as_str(); "cleaned version");
+
+  match SemVersion::parse(&version_string) {
+    Ok(version) => {
+      info!(SCOPE = binary_name.as_ref(), version:display = version, stream:display = stream; "version parsed");
+      Ok(Some(FoundVersion {
+        version,
+        raw,
+        stream,
+      }))
+    }
+    Err(e) => {
+      warn!(SCOPE = binary_name.as_ref(), error:display = e; "error parsing version");
+      Err(
+        VersionError::SemverParse {
+          version_string,
+          source: e,
         }
+        .into(),
+      )
+    }
+  }
+}
+
+/// Probe the version of one found binary, from the cache if it didn't change
+#[cfg(feature = "version-retrieval")]
+fn probe_binary<'a>(
+  binary: Binary<'a>,
+  probes: &Probes,
+  cache: Option<&VersionCache>,
+) -> Binary<'a> {
+  let probe = probes.get(&binary.name).with_override(&binary.probe);
+  if probe.builtin {
+    debug!(SCOPE = binary.name.as_ref(); "builtin, not probing");
+    return binary;
+  }
+  let probed = |found: Option<&FoundVersion>, duration, cached| Probed {
+    args: probe.args.clone(),
+    stream: found.map(|found| found.stream),
+    raw_version: found.map(|found| found.raw.clone()),
+    duration,
+    cached,
+  };
+
+  // binaries that didn't change since the last run aren't called again
+  let cached = match (cache, &binary.path) {
+    (Some(cache), Some(path)) => cache.get(path, &probe),
+    _ => None,
+  };
+  if let Some(found) = cached {
+    return Binary {
+      version: Some(found.version.clone()),
+      probed: Some(probed(Some(&found), Duration::ZERO, true)),
+      ..binary
+    };
+  }
+
+  let now = Instant::now();
+  let result = get_version(binary.name.clone(), &probe);
+  let duration = now.elapsed();
+  match result {
+    Ok(Some(found)) => {
+      if let (Some(cache), Some(path)) = (cache, &binary.path) {
+        cache.insert(path, &probe, &found);
+      }
+      Binary {
+        version: Some(found.version.clone()),
+        probed: Some(probed(Some(&found), duration, false)),
+        ..binary
       }
     }
+    Ok(None) => binary,
+    Err(e) if matches!(e.downcast_ref(), Some(VersionError::Timeout { .. })) => Binary {
+      timed_out: true,
+      probed: Some(probed(None, duration, false)),
+      ..binary
+    },
     Err(e) => {
-      // For binaries known not to have version flags, this is expected
-      if known_binaries().contains(&binary_name) {
-        Ok(None)
-      } else {
-        Err(e)
+      // Log the error but don't fail the entire process
+      warn!(SCOPE = binary.name.as_ref(), error:display = e; "error getting version");
+      Binary {
+        probed: Some(probed(None, duration, false)),
+        ..binary
+      }
+    }
+  }
+}
+
+/// Probe the copies further down the PATH by their path, with the probe of the found binary
+#[cfg(feature = "version-retrieval")]
+fn probe_shadowed<'a>(
+  mut binary: Binary<'a>,
+  probes: &Probes,
+  cache: Option<&VersionCache>,
+) -> Binary<'a> {
+  let probe = probes.get(&binary.name).with_override(&binary.probe);
+  if probe.builtin {
+    return binary;
+  }
+  for copy in binary.shadowed.iter_mut().flatten() {
+    if let Some(found) = cache.and_then(|cache| cache.get(&copy.path, &probe)) {
+      copy.version = Some(found.version);
+      continue;
+    }
+    match get_version(Cow::owned(copy.path.display().to_string()), &probe) {
+      Ok(Some(found)) => {
+        if let Some(cache) = cache {
+          cache.insert(&copy.path, &probe, &found);
+        }
+        copy.version = Some(found.version);
+      }
+      Ok(None) => {}
+      Err(e) => {
+        warn!(SCOPE = binary.name.as_ref(), path:display = copy.path.display(), error:display = e; "error getting version of shadowed copy")
       }
     }
   }
+  binary
 }
 
 #[cfg(feature = "version-retrieval")]
-pub fn get_versions_for_bins(binaries: Vec<Binary>) -> Vec<Binary> {
+pub fn get_versions_for_bins<'a>(
+  binaries: Vec<Binary<'a>>,
+  probes: &Probes,
+  cache: Option<&VersionCache>,
+) -> (Vec<Binary<'a>>, Vec<Binary<'a>>) {
   binaries
     // .into_iter()
     .into_par_iter()
-    .map(|binary| {
-      // filter out known binaries that don't have a version
-      if known_binaries().contains(&binary.name) {
-        return Binary {
-          name: binary.name,
-          version: None,
-          package_manager: binary.package_manager,
-        };
-      }
-
-      let version = match get_version(binary.name.clone()) {
-        Ok(v) => v,
-        Err(e) => {
-          // Log the error but don't fail the entire process
-          warn!(SCOPE = binary.name.as_ref(), error:display = e; "error getting version");
-          None
-        }
-      };
-
-      Binary {
-        name: binary.name,
-        version,
-        package_manager: binary.package_manager,
+    .map(|binary| probe_shadowed(probe_binary(binary, probes, cache), probes, cache))
+    .partition(|binary| {
+      let meets_requirement = binary.meets_requirement();
+      if let Some(ref req) = binary.requirement {
+        info!(
+          SCOPE = binary.name.as_ref(),
+          requirement:display = req,
+          version:debug = binary.version,
+          satisfied = meets_requirement;
+          "checked version requirement"
+        );
       }
+      meets_requirement
     })
-    .collect()
 }
 
 #[cfg(test)]
 mod tests {
   use crate::{cli::Cli, io::get_binary_names};
+  use clap::Parser;
 
   use super::*;
 
@@ -456,6 +642,56 @@ mod tests {
     }
   }
 
+  #[cfg(feature = "version-retrieval")]
+  #[test]
+  fn test_extract_version_with_custom_regex() {
+    let regex = Regex::new(r"build (\d+)").unwrap();
+    let output = "mytool 2024 edition, build 4711\n";
+    let version = extract_version_with(Cow::borrowed(output), Cow::borrowed("mytool"), &regex);
+    assert_eq!(version.unwrap().as_ref(), "4711.0.0");
+
+    let version = extract_version_with(Cow::borrowed("1.2.3"), Cow::borrowed("mytool"), &regex);
+    assert!(version.is_err());
+  }
+
+  #[cfg(all(feature = "version-retrieval", unix))]
+  #[test]
+  fn test_get_version_records_stream() {
+    let probe = Probe {
+      args: vec!["-c".into(), "echo usage; echo tool 1.2.3 >&2".into()],
+      ..Probe::default()
+    };
+    let found = get_version(Cow::borrowed("sh"), &probe).unwrap().unwrap();
+    assert_eq!(found.version, SemVersion::new(1, 2, 3));
+    assert_eq!(found.raw, "1.2.3");
+    assert_eq!(found.stream, Stream::Stderr);
+
+    let stdout_only = Probe {
+      stream: Stream::Stdout,
+      ..probe
+    };
+    assert!(get_version(Cow::borrowed("sh"), &stdout_only).is_err());
+  }
+
+  #[cfg(all(feature = "version-retrieval", unix))]
+  #[test]
+  fn test_execute_binary_timeout() {
+    let probe = Probe {
+      // the background sleep keeps the output pipe open, only killing the group ends it
+      args: vec!["-c".into(), "sleep 10 & read line; sleep 10".into()],
+      timeout: std::time::Duration::from_millis(200),
+      ..Probe::default()
+    };
+    let now = Instant::now();
+    let result = execute_binary("sh", &probe);
+    assert!(now.elapsed() < std::time::Duration::from_secs(5));
+    let err = result.unwrap_err();
+    assert!(matches!(
+      err.downcast_ref(),
+      Some(VersionError::Timeout { .. })
+    ));
+  }
+
   #[cfg(feature = "version-retrieval")]
   #[test]
   fn test_extract_version_no_match_feature_on() {
@@ -472,29 +708,35 @@ mod tests {
     }
   }
 
+  #[cfg(feature = "version-retrieval")]
+  #[test]
+  fn test_get_versions_for_bins_requirement() {
+    if which::which("cargo").is_err() {
+      println!("Skipping requirement check as cargo is not found in PATH");
+      return;
+    }
+    let bins = vec![
+      Binary::new_with_requirement(
+        Cow::borrowed("cargo"),
+        Some(semver::VersionReq::parse(">=0.1").unwrap()),
+      ),
+      Binary::new_with_requirement(
+        Cow::borrowed("cargo"),
+        Some(semver::VersionReq::parse(">=999").unwrap()),
+      ),
+    ];
+    let (up_to_date, outdated) = get_versions_for_bins(bins, &Probes::embedded(), None);
+    assert_eq!(up_to_date.len(), 1);
+    assert_eq!(outdated.len(), 1);
+    assert_eq!(
+      outdated[0].requirement,
+      Some(semver::VersionReq::parse(">=999").unwrap())
+    );
+  }
+
   #[test]
   fn test_get_binary_names_from_args() {
-    let cli = {
-      let bins = Some(vec!["bat".to_string(), "btm".to_string()]);
-      #[cfg(feature = "version-retrieval")]
-      {
-        Cli {
-          bins,
-          quiet: false,
-          verbosity: 0,
-          no_versions: false,
-          full_versions: false,
-        }
-      }
-      #[cfg(not(feature = "version-retrieval"))]
-      {
-        Cli {
-          bins,
-          quiet: false,
-          verbosity: 0,
-        }
-      }
-    };
+    let cli = Cli::parse_from(["needs", "bat", "btm"]);
     let binaries = get_binary_names(&cli).unwrap();
     assert_eq!(binaries.len(), 2);
     assert_eq!(binaries[0].name, Cow::borrowed("bat"));
@@ -505,7 +747,7 @@ mod tests {
   #[test]
   fn test_run_command_with_version_feature_on() {
     let binary_name = "cargo"; // A binary likely present in dev environment
-    let version_output = execute_binary(binary_name);
+    let version_output = execute_binary(binary_name, &Probe::default());
     println!("Version output for {}: {:?}", binary_name, version_output);
     if which::which(binary_name).is_ok() {
       // Only assert if cargo is actually found
@@ -515,7 +757,7 @@ mod tests {
       );
       if let Ok(vo) = version_output {
         assert!(
-          vo.to_lowercase().contains("cargo"),
+          vo.stdout.to_lowercase().contains("cargo"),
           "cargo version output should contain 'cargo'"
         );
       }